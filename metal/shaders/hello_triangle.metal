@@ -2,14 +2,16 @@
 
 using namespace metal;
 
-// Vertex layout
+// Vertex layout, matches Vertex in structs.rs. The float3s are packed, because glam's Vec3 is 12 bytes and a float3 is 16.
 struct vertex_t {
-    float3 position;
-    float3 normal;
+    packed_float3 position;
+    packed_float3 normal;
     float4 tangent;
     float4 color;
     float2 uv0;
     float2 uv1; 
+    uint4 joints;
+    float4 weights;
 };
 
 // Constant buffers
@@ -17,39 +19,236 @@ struct const_buffer_t {
     float4x4 model_matrix;
     float4x4 view_matrix;
     float4x4 proj_matrix;
+    float4x4 normal_matrix; // Inverse transpose of the model matrix
+    uint joint_count; // 0 when the mesh is not skinned
+    uint light_count; // 0 draws the mesh unlit
+    uint2 _padding;
+    float4 camera_position; // In world space, w is unused
+};
+
+// A punctual light, matches LightData in light.rs
+struct light_t {
+    float4 position;  // w is the range, 0 when the light has an infinite range
+    float4 direction; // Direction the light shines in
+    float4 color;     // Color times intensity
+    uint kind;        // 0 = directional, 1 = point, 2 = spot
+    float inner_cone_cos;
+    float outer_cone_cos;
+};
+
+// The factors and UV sets of a material, matches MaterialData in material.rs
+struct material_t {
+    float4 base_color;
+    float4 emissive; // w is unused
+    float metallic;
+    float roughness;
+    float normal_scale; // 0 when there is no normal map
+    float occlusion_strength;
+    uint4 tex_coords; // UV set of the base color, metallic/roughness, normal and occlusion textures
+    uint tex_coord_emm;
+    uint alpha_mode; // 0 = opaque, 1 = mask, 2 = blend
+    float alpha_cutoff; // Only used by masked materials
+    uint double_sided; // 1 when back faces are drawn, with a flipped normal
 };
 
 // Data that's passed from the vertex shader to the fragment shader
 struct vertex_shader_output_t {
     float4 position [[position]];
+    float point_size [[point_size]]; // Only used when drawing points
     float4 color;
+    float3 world_position;
+    float3 normal;
+    float4 tangent; // w is the handedness of the bitangent
     float2 uv0;
+    float2 uv1;
 };
 
 // Vertex shader function
 vertex vertex_shader_output_t hello_triangle_vertex(
     const device vertex_t* vertex_array [[buffer(0)]], 
     const constant const_buffer_t* const_buffer [[buffer(1)]],
+    const device float4x4* joint_matrices [[buffer(2)]],
     uint vertex_index [[vertex_id]]
 ) {
     vertex_shader_output_t out;
     const device vertex_t& vtx = vertex_array[vertex_index];
-    float shading = dot(vtx.normal, normalize(float3(0.3, 0.3, 0.3)));
-    shading = clamp(shading, 0.0, 1.0);
-    out.color = float4(vtx.color.r, vtx.color.g, vtx.color.b, 1.0);
+    out.color = vtx.color;
     out.position = float4(vtx.position.x, vtx.position.y, vtx.position.z, 1.0);
+    float4 normal = float4(float3(vtx.normal), 0.0);
+    float4 tangent = float4(vtx.tangent.xyz, 0.0);
+    if (const_buffer->joint_count > 0) {
+        // Linear blend skinning, the same as skin_vertex() in skin.rs
+        float4x4 skin_matrix = joint_matrices[vtx.joints.x] * vtx.weights.x
+                             + joint_matrices[vtx.joints.y] * vtx.weights.y
+                             + joint_matrices[vtx.joints.z] * vtx.weights.z
+                             + joint_matrices[vtx.joints.w] * vtx.weights.w;
+        out.position *= skin_matrix;
+        normal *= skin_matrix;
+        tangent *= skin_matrix;
+    }
     out.position *= const_buffer->model_matrix;
+    out.world_position = out.position.xyz;
+    out.normal = (normal * const_buffer->normal_matrix).xyz;
+    out.tangent = float4((tangent * const_buffer->model_matrix).xyz, vtx.tangent.w);
     out.position *= const_buffer->view_matrix;
     out.position *= const_buffer->proj_matrix;
     out.uv0 = float2(vtx.uv0.x, vtx.uv0.y);
+    out.uv1 = float2(vtx.uv1.x, vtx.uv1.y);
+    out.point_size = 1.0;
     return out;
 }
 
+// The light that reaches a surface point, and the direction towards the light. The same as PlacedLight::incoming_light() in light.rs
+float3 incoming_light(const constant light_t& light, float3 position, thread float3& to_light) {
+    if (light.kind == 0) {
+        to_light = -normalize(light.direction.xyz);
+        return light.color.rgb;
+    }
+
+    // Inverse square falloff, smoothly cut off at the range
+    float3 delta = light.position.xyz - position;
+    float distance_squared = max(dot(delta, delta), 0.0001);
+    to_light = delta / sqrt(distance_squared);
+    float attenuation = 1.0 / distance_squared;
+    float range = light.position.w;
+    if (range > 0.0) {
+        float window = clamp(1.0 - pow(distance_squared / (range * range), 2.0), 0.0, 1.0);
+        attenuation *= window * window;
+    }
+
+    // Spot lights fade out between the inner and outer cone
+    if (light.kind == 2) {
+        float scale = 1.0 / max(light.inner_cone_cos - light.outer_cone_cos, 0.001);
+        float offset = -light.outer_cone_cos * scale;
+        float cone = clamp(dot(normalize(light.direction.xyz), -to_light) * scale + offset, 0.0, 1.0);
+        attenuation *= cone * cone;
+    }
+    return light.color.rgb * attenuation;
+}
+
+// The glTF metallic-roughness BRDF, the same as brdf() in pbr.rs
+constant float min_roughness = 0.03;
+
+float distribution_ggx(float n_dot_h, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float f = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (M_PI_F * f * f);
+}
+
+float visibility_smith_ggx(float n_dot_l, float n_dot_v, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared);
+    float ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared);
+    float sum = ggx_v + ggx_l;
+    if (sum <= 0.0) {
+        return 0.0;
+    }
+    return 0.5 / sum;
+}
+
+float3 brdf(float3 base_color, float metallic, float roughness, float3 normal, float3 to_view, float3 to_light) {
+    float n_dot_l = dot(normal, to_light);
+    if (n_dot_l <= 0.0) {
+        return float3(0.0);
+    }
+    float n_dot_v = clamp(dot(normal, to_view), 0.0, 1.0);
+    float3 half_vector = to_view + to_light;
+    half_vector = length(half_vector) > 0.0 ? normalize(half_vector) : float3(0.0);
+    float n_dot_h = clamp(dot(normal, half_vector), 0.0, 1.0);
+    float v_dot_h = clamp(dot(to_view, half_vector), 0.0, 1.0);
+
+    float3 diffuse_color = mix(base_color, float3(0.0), metallic);
+    float3 f0 = mix(float3(0.04), base_color, metallic);
+    float3 fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+
+    float alpha = pow(clamp(roughness, min_roughness, 1.0), 2.0);
+    float3 specular = fresnel * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+    float3 diffuse = (1.0 - fresnel) * diffuse_color / M_PI_F;
+    return diffuse + specular;
+}
+
+// The same as perturb_normal() in pbr.rs
+float3 perturb_normal(float3 normal, float4 tangent, float3 normal_sample, float scale) {
+    if (length(normal) == 0.0) {
+        return normal;
+    }
+    normal = normalize(normal);
+    float3 tangent_xyz = tangent.xyz - normal * dot(normal, tangent.xyz);
+    if (length(tangent_xyz) == 0.0) {
+        return normal;
+    }
+    tangent_xyz = normalize(tangent_xyz);
+    float3 bitangent = cross(normal, tangent_xyz) * (tangent.w < 0.0 ? -1.0 : 1.0);
+    float3 bent = (normal_sample * 2.0 - 1.0) * float3(scale, scale, 1.0);
+    float3 result = tangent_xyz * bent.x + bitangent * bent.y + normal * bent.z;
+    return length(result) > 0.0 ? normalize(result) : normal;
+}
+
+float3 srgb_to_linear(float3 color) {
+    return select(pow((color + 0.055) / 1.055, 2.4), color / 12.92, color <= 0.04045);
+}
+
+float3 linear_to_srgb(float3 color) {
+    return select(1.055 * pow(color, 1.0 / 2.4) - 0.055, color * 12.92, color <= 0.0031308);
+}
+
+float2 select_uv(vertex_shader_output_t in, uint tex_coord) {
+    return tex_coord == 1 ? in.uv1 : in.uv0;
+}
+
 // Fragment shader function
 fragment float4 hello_triangle_fragment(
     vertex_shader_output_t in [[stage_in]],
-    texture2d<float> tex_color [[texture(0)]]
+    bool front_facing [[front_facing]],
+    texture2d<float> tex_alb [[texture(0)]],
+    texture2d<float> tex_mtl_rgh [[texture(1)]],
+    texture2d<float> tex_nrm [[texture(2)]],
+    texture2d<float> tex_occ [[texture(3)]],
+    texture2d<float> tex_emm [[texture(4)]],
+    sampler smp_alb [[sampler(0)]],
+    sampler smp_mtl_rgh [[sampler(1)]],
+    sampler smp_nrm [[sampler(2)]],
+    sampler smp_occ [[sampler(3)]],
+    sampler smp_emm [[sampler(4)]],
+    const constant light_t* lights [[buffer(0)]],
+    const constant const_buffer_t* const_buffer [[buffer(1)]],
+    const constant material_t& material [[buffer(2)]]
 ) {
-    constexpr sampler texture_sampler (mag_filter::nearest, min_filter::nearest);
-    return in.color * tex_color.sample(texture_sampler, in.uv0);
-}
\ No newline at end of file
+    float4 albedo = tex_alb.sample(smp_alb, select_uv(in, material.tex_coords.x));
+    float4 base_color = in.color * float4(srgb_to_linear(albedo.rgb), albedo.a) * material.base_color;
+
+    // Only blended materials keep their alpha, masked ones are opaque where they aren't cut out
+    if (material.alpha_mode == 1 && base_color.a < material.alpha_cutoff) {
+        discard_fragment();
+    }
+    float alpha = material.alpha_mode == 2 ? base_color.a : 1.0;
+    if (const_buffer->light_count == 0) {
+        return float4(linear_to_srgb(base_color.rgb), alpha);
+    }
+
+    // The back faces of double sided materials face the other way
+    bool flip = !front_facing && material.double_sided != 0;
+    float3 in_normal = flip ? -in.normal : in.normal;
+    float4 in_tangent = flip ? -in.tangent : in.tangent;
+
+    // Roughness is in the green channel and metallic in the blue one, so they can share a texture with occlusion in red
+    float4 metallic_roughness = tex_mtl_rgh.sample(smp_mtl_rgh, select_uv(in, material.tex_coords.y));
+    float3 normal_sample = tex_nrm.sample(smp_nrm, select_uv(in, material.tex_coords.z)).rgb;
+    float occlusion = tex_occ.sample(smp_occ, select_uv(in, material.tex_coords.w)).r;
+    float3 emissive = srgb_to_linear(tex_emm.sample(smp_emm, select_uv(in, material.tex_coord_emm)).rgb) * material.emissive.rgb;
+    float metallic = clamp(material.metallic * metallic_roughness.b, 0.0, 1.0);
+    float roughness = clamp(material.roughness * metallic_roughness.g, 0.0, 1.0);
+    float3 normal = perturb_normal(in_normal, in_tangent, normal_sample, material.normal_scale);
+    occlusion = 1.0 + material.occlusion_strength * (occlusion - 1.0);
+
+    // Summed over every light, the same as shade_surface() in pbr.rs
+    float3 to_view = const_buffer->camera_position.xyz - in.world_position;
+    to_view = length(to_view) > 0.0 ? normalize(to_view) : float3(0.0);
+    float3 result = float3(0.0);
+    for (uint i = 0; i < const_buffer->light_count; i++) {
+        float3 to_light;
+        float3 radiance = incoming_light(lights[i], in.world_position, to_light);
+        result += brdf(base_color.rgb, metallic, roughness, normal, to_view, to_light) * radiance * max(dot(normal, to_light), 0.0);
+    }
+    return float4(linear_to_srgb(result * occlusion + emissive), alpha);
+}
@@ -0,0 +1,137 @@
+use gltf::accessor::DataType;
+use gltf::buffer::Data;
+
+use crate::error::AssetError;
+
+// Reads every component of every element of an accessor, in order. This handles the accessor's byte offset,
+// interleaved buffer views (byte stride), and sparse accessors. Values are not normalized yet.
+// f64 can hold any of the component types exactly, so this works for both floats and indices.
+fn read_accessor_raw(accessor: &gltf::Accessor, mesh_data: &[Data]) -> Result<Vec<f64>, AssetError> {
+    let component_count = accessor.dimensions().multiplicity();
+    let component_size = accessor.data_type().size();
+    let element_size = component_count * component_size;
+    let mut values = vec![0.0; accessor.count() * component_count];
+
+    // Accessors without a buffer view are all zeros, unless sparse values are applied on top
+    if let Some(view) = accessor.view() {
+        let buffer = view_bytes(&view, mesh_data)?;
+        let stride = view.stride().unwrap_or(element_size);
+        if stride < element_size {
+            return Err(AssetError::MalformedData(format!(
+                "buffer view {} has a byte stride of {stride}, but accessor {} elements are {element_size} bytes",
+                view.index(),
+                accessor.index()
+            )));
+        }
+
+        for element in 0..accessor.count() {
+            let start = accessor.offset() + element * stride;
+            let bytes = buffer.get(start..start + element_size).ok_or(AssetError::MalformedData(format!(
+                "accessor {} reads past the end of buffer view {}",
+                accessor.index(),
+                view.index()
+            )))?;
+            for (component, component_bytes) in bytes.chunks_exact(component_size).enumerate() {
+                values[element * component_count + component] = read_component(component_bytes, accessor.data_type());
+            }
+        }
+    }
+
+    if let Some(sparse) = accessor.sparse() {
+        // Find which elements are replaced
+        let index_view = sparse.indices().view();
+        let index_type = match sparse.indices().index_type() {
+            gltf::accessor::sparse::IndexType::U8 => DataType::U8,
+            gltf::accessor::sparse::IndexType::U16 => DataType::U16,
+            gltf::accessor::sparse::IndexType::U32 => DataType::U32,
+        };
+        let index_bytes = view_bytes(&index_view, mesh_data)?
+            .get(sparse.indices().offset()..)
+            .and_then(|bytes| bytes.get(..sparse.count() * index_type.size()))
+            .ok_or(AssetError::MalformedData(format!("sparse indices of accessor {} are out of bounds", accessor.index())))?;
+
+        // And replace them with the sparse values, which are always tightly packed
+        let value_view = sparse.values().view();
+        let value_bytes = view_bytes(&value_view, mesh_data)?
+            .get(sparse.values().offset()..)
+            .and_then(|bytes| bytes.get(..sparse.count() * element_size))
+            .ok_or(AssetError::MalformedData(format!("sparse values of accessor {} are out of bounds", accessor.index())))?;
+
+        for (index_bytes, element_bytes) in index_bytes.chunks_exact(index_type.size()).zip(value_bytes.chunks_exact(element_size)) {
+            let element = read_component(index_bytes, index_type) as usize;
+            if element >= accessor.count() {
+                return Err(AssetError::MalformedData(format!(
+                    "sparse index {element} is out of range for accessor {} with {} elements",
+                    accessor.index(),
+                    accessor.count()
+                )));
+            }
+            for (component, component_bytes) in element_bytes.chunks_exact(component_size).enumerate() {
+                values[element * component_count + component] = read_component(component_bytes, accessor.data_type());
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+// Reads an accessor as floats. Normalized integer components are mapped to 0..1 (unsigned) or -1..1 (signed), like the spec says.
+pub(crate) fn read_accessor_f32(accessor: &gltf::Accessor, mesh_data: &[Data]) -> Result<Vec<f32>, AssetError> {
+    let values = read_accessor_raw(accessor, mesh_data)?;
+    if !accessor.normalized() {
+        return Ok(values.into_iter().map(|value| value as f32).collect());
+    }
+
+    let max = match accessor.data_type() {
+        DataType::I8 => i8::MAX as f64,
+        DataType::U8 => u8::MAX as f64,
+        DataType::I16 => i16::MAX as f64,
+        DataType::U16 => u16::MAX as f64,
+        data_type => {
+            return Err(AssetError::MalformedData(format!(
+                "accessor {} is normalized, but has {data_type:?} components",
+                accessor.index()
+            )));
+        }
+    };
+    Ok(values.into_iter().map(|value| (value / max).max(-1.0) as f32).collect())
+}
+
+// Reads an accessor of unsigned integers, like indices
+pub(crate) fn read_accessor_u32(accessor: &gltf::Accessor, mesh_data: &[Data]) -> Result<Vec<u32>, AssetError> {
+    match accessor.data_type() {
+        DataType::U8 | DataType::U16 | DataType::U32 => {}
+        data_type => {
+            return Err(AssetError::MalformedData(format!(
+                "accessor {} should contain unsigned integers, but has {data_type:?} components",
+                accessor.index()
+            )));
+        }
+    }
+    Ok(read_accessor_raw(accessor, mesh_data)?.into_iter().map(|value| value as u32).collect())
+}
+
+// Finds the bytes a buffer view covers
+pub(crate) fn view_bytes<'a>(view: &gltf::buffer::View, mesh_data: &'a [Data]) -> Result<&'a [u8], AssetError> {
+    let buffer_index = view.buffer().index();
+    let buffer = &mesh_data
+        .get(buffer_index)
+        .ok_or(AssetError::MalformedData(format!("buffer {buffer_index} does not exist")))?
+        .0;
+    buffer.get(view.offset()..view.offset() + view.length()).ok_or(AssetError::MalformedData(format!(
+        "buffer view {} is out of bounds of buffer {buffer_index}",
+        view.index()
+    )))
+}
+
+// glTF data is always little endian
+fn read_component(bytes: &[u8], data_type: DataType) -> f64 {
+    match data_type {
+        DataType::I8 => i8::from_le_bytes([bytes[0]]) as f64,
+        DataType::U8 => bytes[0] as f64,
+        DataType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+        DataType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+        DataType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        DataType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+    }
+}
@@ -0,0 +1,229 @@
+use glam::{Quat, Vec3};
+
+use crate::accessor::read_accessor_f32;
+use crate::error::AssetError;
+use crate::mesh::Model;
+use crate::scene::Node;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,        // Holds the value of the previous keyframe
+    Linear,      // Lerps between keyframes, rotations are slerped
+    CubicSpline, // Hermite spline, every keyframe stores an in-tangent, value and out-tangent
+}
+
+// Which part of a node a channel animates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    Weights, // Morph target weights
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: usize, // Index into Model::nodes
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,  // Keyframe times in seconds, in increasing order
+    pub values: Vec<f32>, // Keyframe values, with `components` values per keyframe (times 3 for cubic splines)
+    pub components: usize,
+}
+
+// A clip of channels that play together, like "walk" or "open door"
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    pub duration: f32, // Time of the last keyframe of any channel
+}
+
+impl Channel {
+    // Samples the channel at a time in seconds. Times outside the keyframes hold the first or last value.
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let n = self.components;
+        let last = self.times.len() - 1;
+        if time <= self.times[0] || last == 0 {
+            return self.keyframe_value(0).to_vec();
+        }
+        if time >= self.times[last] {
+            return self.keyframe_value(last).to_vec();
+        }
+
+        // Find the keyframes before and after this time
+        let next = self.times.partition_point(|keyframe_time| *keyframe_time <= time);
+        let prev = next - 1;
+        let delta = self.times[next] - self.times[prev];
+        let t = (time - self.times[prev]) / delta;
+
+        let (a, b) = (self.keyframe_value(prev), self.keyframe_value(next));
+        let mut result = match self.interpolation {
+            Interpolation::Step => a.to_vec(),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let rotation = Quat::from_slice(a).slerp(Quat::from_slice(b), t);
+                rotation.to_array().to_vec()
+            }
+            Interpolation::Linear => (0..n).map(|i| a[i] + (b[i] - a[i]) * t).collect(),
+            Interpolation::CubicSpline => {
+                let out_tangent = &self.values[prev * n * 3 + n * 2..prev * n * 3 + n * 3];
+                let in_tangent = &self.values[next * n * 3..next * n * 3 + n];
+                let (t2, t3) = (t * t, t * t * t);
+                (0..n)
+                    .map(|i| {
+                        (2.0 * t3 - 3.0 * t2 + 1.0) * a[i]
+                            + (t3 - 2.0 * t2 + t) * delta * out_tangent[i]
+                            + (-2.0 * t3 + 3.0 * t2) * b[i]
+                            + (t3 - t2) * delta * in_tangent[i]
+                    })
+                    .collect()
+            }
+        };
+
+        // Splines don't keep quaternions at unit length
+        if self.property == Property::Rotation && self.interpolation == Interpolation::CubicSpline {
+            let rotation = Quat::from_slice(&result).normalize();
+            result = rotation.to_array().to_vec();
+        }
+        return result;
+    }
+
+    // The value of a keyframe, skipping the tangents of cubic splines
+    fn keyframe_value(&self, keyframe: usize) -> &[f32] {
+        let n = self.components;
+        match self.interpolation {
+            Interpolation::CubicSpline => &self.values[keyframe * n * 3 + n..keyframe * n * 3 + n * 2],
+            _ => &self.values[keyframe * n..keyframe * n + n],
+        }
+    }
+}
+
+impl Animation {
+    // Poses the nodes the way the animation has them at a time in seconds
+    pub fn apply(&self, time: f32, nodes: &mut [Node]) {
+        for channel in &self.channels {
+            let value = channel.sample(time);
+            let node = &mut nodes[channel.node];
+            match channel.property {
+                Property::Translation => node.transform.translation = Vec3::from_slice(&value),
+                Property::Rotation => node.transform.rotation = Quat::from_slice(&value),
+                Property::Scale => node.transform.scale = Vec3::from_slice(&value),
+                Property::Weights => node.weights = value,
+            }
+        }
+    }
+}
+
+// Plays an animation of a model, keeping track of the clip time
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub animation: usize, // Index into Model::animations
+    pub time: f32,        // Current time in the clip, in seconds
+    pub speed: f32,       // Multiplier for the delta time, negative values play the clip backwards
+    pub looping: bool,    // Wrap around at the end of the clip, instead of holding the last frame
+    pub paused: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(animation: usize) -> AnimationPlayer {
+        AnimationPlayer {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            paused: false,
+        }
+    }
+
+    // Advances the clip time and poses the model's nodes. Pass a delta time of 0 to just apply the current time.
+    pub fn update(&mut self, delta_time: f32, model: &mut Model) {
+        let Some(animation) = model.animations.get(self.animation) else {
+            return;
+        };
+        if !self.paused {
+            self.time += delta_time * self.speed;
+        }
+
+        // Wrap around or hold at the ends of the clip
+        if self.looping && animation.duration > 0.0 {
+            self.time = self.time.rem_euclid(animation.duration);
+        } else {
+            self.time = self.time.clamp(0.0, animation.duration);
+        }
+        animation.apply(self.time, &mut model.nodes);
+    }
+
+    // Jumps to a time in the clip, the next update wraps or clamps it to the clip's duration
+    pub fn seek(&mut self, time: f32) {
+        self.time = time;
+    }
+}
+
+impl Model {
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|animation| animation.name == name)
+    }
+}
+
+pub(crate) fn load_animations(document: &gltf::Document, mesh_data: &[gltf::buffer::Data]) -> Result<Vec<Animation>, AssetError> {
+    let mut animations = Vec::new();
+    for animation in document.animations() {
+        let mut channels = Vec::new();
+        for channel in animation.channels() {
+            let sampler = channel.sampler();
+            let times = read_accessor_f32(&sampler.input(), mesh_data)?;
+            let values = read_accessor_f32(&sampler.output(), mesh_data)?;
+
+            // Sampling looks up keyframes by time, which only works when the times are strictly increasing
+            if times.iter().any(|time| !time.is_finite()) || times.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(AssetError::MalformedData(format!(
+                    "animation {} has a channel with keyframe times that aren't increasing: {times:?}",
+                    animation.index()
+                )));
+            }
+            let property = match channel.target().property() {
+                gltf::animation::Property::Translation => Property::Translation,
+                gltf::animation::Property::Rotation => Property::Rotation,
+                gltf::animation::Property::Scale => Property::Scale,
+                gltf::animation::Property::MorphTargetWeights => Property::Weights,
+            };
+            let interpolation = match sampler.interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+
+            // Weights have one value per morph target, which we can only tell from the amount of values
+            let values_per_keyframe = if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+            let components = match property {
+                Property::Translation | Property::Scale => 3,
+                Property::Rotation => 4,
+                Property::Weights => values.len() / (times.len() * values_per_keyframe).max(1),
+            };
+            if times.is_empty() || components == 0 || values.len() != times.len() * components * values_per_keyframe {
+                return Err(AssetError::MalformedData(format!(
+                    "animation {} has a channel with {} keyframes, but {} values",
+                    animation.index(),
+                    times.len(),
+                    values.len()
+                )));
+            }
+
+            channels.push(Channel {
+                node: channel.target().node().index(),
+                property,
+                interpolation,
+                times,
+                values,
+                components,
+            });
+        }
+
+        animations.push(Animation {
+            name: String::from(animation.name().unwrap_or("untitled")),
+            duration: channels.iter().map(|channel| *channel.times.last().unwrap()).fold(0.0, f32::max),
+            channels,
+        });
+    }
+    return Ok(animations);
+}
@@ -0,0 +1,98 @@
+use std::f32::consts::PI;
+
+use glam::{Mat4, Vec3};
+
+use crate::mesh::Model;
+use crate::structs::Transform;
+
+// How a camera maps view space to clip space. Depth ends up in 0..1, like Metal expects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective {
+        yfov: f32,                 // Vertical field of view in radians
+        aspect_ratio: Option<f32>, // Width / height, uses the framebuffer's aspect ratio when None
+        znear: f32,
+        zfar: Option<f32>,         // None means an infinite far plane
+    },
+    Orthographic {
+        xmag: f32, // Half the width of the view volume
+        ymag: f32, // Half the height of the view volume
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+// A camera defined in a glTF file. Nodes refer to it by index, and the node's transform places it in the scene.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub name: String,
+    pub projection: Projection,
+}
+
+impl Default for Projection {
+    // 45 degree field of view that fits the framebuffer
+    fn default() -> Self {
+        Projection::Perspective {
+            yfov: PI / 4.0,
+            aspect_ratio: None,
+            znear: 0.1,
+            zfar: Some(1000.0),
+        }
+    }
+}
+
+impl Projection {
+    pub fn matrix(&self, framebuffer_aspect_ratio: f32) -> Mat4 {
+        match *self {
+            Projection::Perspective { yfov, aspect_ratio, znear, zfar } => {
+                let aspect_ratio = aspect_ratio.unwrap_or(framebuffer_aspect_ratio);
+                match zfar {
+                    Some(zfar) => Mat4::perspective_rh(yfov, aspect_ratio, znear, zfar),
+                    None => Mat4::perspective_infinite_rh(yfov, aspect_ratio, znear),
+                }
+            }
+            Projection::Orthographic { xmag, ymag, znear, zfar } => Mat4::orthographic_rh(-xmag, xmag, -ymag, ymag, znear, zfar),
+        }
+    }
+}
+
+impl Model {
+    // Where a camera of this model is in the scene, relative to the model. Scale is removed, since it would distort the view.
+    // Returns None when no node in the scene uses the camera. Make sure the world matrices are up to date first.
+    pub fn camera_transform(&self, camera: usize) -> Option<Transform> {
+        let node = self.scene_nodes().into_iter().find(|node| self.nodes[*node].camera == Some(camera))?;
+        let (_, rotation, translation) = self.nodes[node].world_matrix.to_scale_rotation_translation();
+        Some(Transform {
+            translation,
+            rotation,
+            scale: Vec3::ONE,
+        })
+    }
+
+    pub fn find_camera(&self, name: &str) -> Option<usize> {
+        self.cameras.iter().position(|camera| camera.name == name)
+    }
+}
+
+pub(crate) fn load_cameras(document: &gltf::Document) -> Vec<Camera> {
+    document
+        .cameras()
+        .map(|camera| Camera {
+            name: String::from(camera.name().unwrap_or("untitled")),
+            projection: match camera.projection() {
+                gltf::camera::Projection::Perspective(perspective) => Projection::Perspective {
+                    yfov: perspective.yfov(),
+                    aspect_ratio: perspective.aspect_ratio(),
+                    znear: perspective.znear(),
+                    zfar: perspective.zfar(),
+                },
+                gltf::camera::Projection::Orthographic(orthographic) => Projection::Orthographic {
+                    xmag: orthographic.xmag(),
+                    ymag: orthographic.ymag(),
+                    znear: orthographic.znear(),
+                    zfar: orthographic.zfar(),
+                },
+            },
+        })
+        .collect()
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+use std::path::PathBuf;
+
+// Everything that can go wrong while loading an asset. None of the loaders panic on bad input, they return one of these instead.
+#[derive(Debug)]
+pub enum AssetError {
+    // The file could not be read
+    Io { path: PathBuf, source: std::io::Error },
+    // The file was read, but is not valid glTF/image data
+    Parse { path: PathBuf, message: String },
+    // The data is valid, but uses a feature or format we don't support
+    UnsupportedFormat(String),
+    // The data parsed, but references things that don't exist or has inconsistent sizes
+    MalformedData(String),
+}
+
+impl AssetError {
+    pub(crate) fn from_gltf(path: PathBuf, err: gltf::Error) -> AssetError {
+        match err {
+            gltf::Error::Io(source) => AssetError::Io { path, source },
+            gltf::Error::UnsupportedImageEncoding | gltf::Error::UnsupportedImageFormat(_) | gltf::Error::UnsupportedScheme => {
+                AssetError::UnsupportedFormat(err.to_string())
+            }
+            gltf::Error::BufferLength { .. } | gltf::Error::MissingBlob => AssetError::MalformedData(err.to_string()),
+            err => AssetError::Parse { path, message: err.to_string() },
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Io { path, source } => write!(f, "failed to read \"{}\": {source}", path.display()),
+            AssetError::Parse { path, message } => write!(f, "failed to parse \"{}\": {message}", path.display()),
+            AssetError::UnsupportedFormat(message) => write!(f, "unsupported format: {message}"),
+            AssetError::MalformedData(message) => write!(f, "malformed data: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::{Path, PathBuf};
+
+use glam::{Quat, Vec3};
+
+use crate::graphics::ModelQueueEntry;
+use crate::headless;
+use crate::screenshot;
+use crate::structs::Transform;
+
+pub const GOLDEN_WIDTH: u32 = 320;
+pub const GOLDEN_HEIGHT: u32 = 180;
+
+// How far a rendered frame is allowed to drift from its reference image before the test fails
+#[derive(Debug, Copy, Clone)]
+pub struct GoldenTolerance {
+    pub per_channel: u8,            // A pixel counts as different when any channel differs by more than this
+    pub max_differing_pixels: f32,  // Fraction of the pixels that is allowed to be different, from 0.0 to 1.0
+    pub min_ssim: f32,              // Lowest mean structural similarity that is still accepted, from 0.0 to 1.0
+}
+
+impl Default for GoldenTolerance {
+    fn default() -> Self {
+        GoldenTolerance {
+            per_channel: 8,
+            max_differing_pixels: 0.001,
+            min_ssim: 0.99,
+        }
+    }
+}
+
+pub struct ImageComparison {
+    pub total_pixels: usize,
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+    pub ssim: f32,
+    pub diff_image: Vec<u8>, // RGBA8, differing pixels are red, the rest is the reference image dimmed to grayscale
+}
+
+impl ImageComparison {
+    pub fn passes(&self, tolerance: &GoldenTolerance) -> bool {
+        let differing_fraction = self.differing_pixels as f32 / self.total_pixels.max(1) as f32;
+        differing_fraction <= tolerance.max_differing_pixels && self.ssim >= tolerance.min_ssim
+    }
+}
+
+// A single scene that is rendered and compared against tests/golden/<name>.png
+pub struct GoldenScene {
+    pub name: &'static str,
+    pub model_path: &'static str,
+    pub model_transform: Transform,
+    pub camera: Transform,
+}
+
+pub fn golden_scenes() -> Vec<GoldenScene> {
+    let camera = Transform {
+        translation: Vec3 {x: 0.0, y: 0.0, z: 0.5},
+        rotation: Quat::IDENTITY,
+        scale: Vec3 {x: 1.0, y: 1.0, z: 1.0},
+    };
+    vec![
+        GoldenScene {
+            name: "suzanne",
+            model_path: "./assets/suzanne.gltf",
+            model_transform: Transform {
+                translation: Vec3{x: 0.0, y: 0.0, z: 0.0},
+                rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.5, 0.0),
+                scale: Vec3{x: 0.1, y: 0.1, z: 0.1},
+            },
+            camera,
+        },
+        GoldenScene {
+            name: "sub_nivis_gun",
+            model_path: "./assets/sub_nivis_gun.gltf",
+            model_transform: Transform {
+                translation: Vec3{x: 0.0, y: 0.0, z: 0.0},
+                rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.5, 0.0),
+                scale: Vec3{x: 1.0, y: 1.0, z: 1.0},
+            },
+            camera,
+        },
+        // Lit by the point light in the model, with a rough and a glossy dielectric and a metal
+        GoldenScene {
+            name: "lit_spheres",
+            model_path: "./assets/lit_spheres.gltf",
+            model_transform: Transform {
+                translation: Vec3{x: 0.0, y: 0.0, z: 0.0},
+                rotation: Quat::IDENTITY,
+                scale: Vec3{x: 1.0, y: 1.0, z: 1.0},
+            },
+            camera,
+        },
+    ]
+}
+
+// Renders a golden scene to RGBA8 pixels. This always uses the software renderer, so the output is the same on every machine.
+pub fn render_scene(scene: &GoldenScene, width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mut renderer = headless::create_renderer(width, height, true).map_err(|err| err.to_string())?;
+    let model = renderer.load_model(Path::new(scene.model_path)).map_err(|err| err.to_string())?;
+
+    renderer.update_camera(&scene.camera);
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry {
+        model,
+        transform: scene.model_transform,
+    });
+    renderer.end_frame();
+    renderer.read_framebuffer().ok_or("The renderer can not read back its framebuffer".to_string())
+}
+
+pub fn compare_images(width: u32, height: u32, expected: &[u8], actual: &[u8], tolerance: &GoldenTolerance) -> ImageComparison {
+    let mut comparison = ImageComparison {
+        total_pixels: width as usize * height as usize,
+        differing_pixels: 0,
+        max_difference: 0,
+        ssim: structural_similarity(width, height, expected, actual),
+        diff_image: Vec::with_capacity(expected.len()),
+    };
+
+    for (pixel_expected, pixel_actual) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let difference = pixel_expected.iter()
+            .zip(pixel_actual)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        comparison.max_difference = comparison.max_difference.max(difference);
+
+        if difference > tolerance.per_channel {
+            comparison.differing_pixels += 1;
+            comparison.diff_image.extend_from_slice(&[128 + difference / 2, 0, 0, 255]);
+        } else {
+            let gray = (luminance(pixel_expected) / 4.0) as u8;
+            comparison.diff_image.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    comparison
+}
+
+// Mean structural similarity (SSIM) of the luminance of two RGBA8 images, computed over 8x8 windows.
+// 1.0 means the images are identical, lower values mean they look less alike.
+pub fn structural_similarity(width: u32, height: u32, a: &[u8], b: &[u8]) -> f32 {
+    const WINDOW_SIZE: usize = 8;
+    const C1: f32 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f32 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = (width as usize, height as usize);
+    let luma_a: Vec<f32> = a.chunks_exact(4).map(luminance).collect();
+    let luma_b: Vec<f32> = b.chunks_exact(4).map(luminance).collect();
+
+    let mut ssim_total = 0.0;
+    let mut window_count = 0;
+    for window_y in (0..height).step_by(WINDOW_SIZE) {
+        for window_x in (0..width).step_by(WINDOW_SIZE) {
+            // Gather the statistics of this window
+            let mut samples = Vec::with_capacity(WINDOW_SIZE * WINDOW_SIZE);
+            for y in window_y..(window_y + WINDOW_SIZE).min(height) {
+                for x in window_x..(window_x + WINDOW_SIZE).min(width) {
+                    samples.push((luma_a[x + y * width], luma_b[x + y * width]));
+                }
+            }
+            let n = samples.len() as f32;
+            let mean_a = samples.iter().map(|s| s.0).sum::<f32>() / n;
+            let mean_b = samples.iter().map(|s| s.1).sum::<f32>() / n;
+            let variance_a = samples.iter().map(|s| (s.0 - mean_a).powi(2)).sum::<f32>() / n;
+            let variance_b = samples.iter().map(|s| (s.1 - mean_b).powi(2)).sum::<f32>() / n;
+            let covariance = samples.iter().map(|s| (s.0 - mean_a) * (s.1 - mean_b)).sum::<f32>() / n;
+
+            ssim_total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+            window_count += 1;
+        }
+    }
+
+    if window_count == 0 {
+        return 1.0;
+    }
+    return ssim_total / window_count as f32;
+}
+
+fn luminance(pixel: &[u8]) -> f32 {
+    0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32
+}
+
+pub struct GoldenOptions {
+    pub reference_dir: PathBuf,
+    pub output_dir: PathBuf, // Where the actual and diff images of failed scenes are written
+    pub update: bool,        // Overwrite the reference images instead of comparing against them
+    pub tolerance: GoldenTolerance,
+}
+
+impl GoldenOptions {
+    // Parses `--golden <reference dir> [--output <dir>] [--update] [--tolerance <0-255>] [--max-differing <fraction>] [--min-ssim <0-1>]`.
+    // Returns Ok(None) when the arguments don't start with --golden.
+    pub fn from_args(args: &[String]) -> Result<Option<GoldenOptions>, String> {
+        if args.first().map(String::as_str) != Some("--golden") {
+            return Ok(None);
+        }
+
+        let mut args = args.iter().skip(1);
+        let mut options = GoldenOptions {
+            reference_dir: PathBuf::from(args.next().ok_or("--golden expects a reference directory")?),
+            output_dir: PathBuf::from("target/golden"),
+            update: false,
+            tolerance: GoldenTolerance::default(),
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--output" => options.output_dir = PathBuf::from(args.next().ok_or("--output expects a directory")?),
+                "--update" => options.update = true,
+                "--tolerance" => options.tolerance.per_channel = parse_arg(arg, args.next())?,
+                "--max-differing" => options.tolerance.max_differing_pixels = parse_arg(arg, args.next())?,
+                "--min-ssim" => options.tolerance.min_ssim = parse_arg(arg, args.next())?,
+                _ => return Err(format!("Unknown argument \"{arg}\"")),
+            }
+        }
+        return Ok(Some(options));
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(name: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or(format!("{name} expects a value"))?;
+    value.parse().map_err(|_| format!("Invalid value \"{value}\" for {name}"))
+}
+
+// Renders every golden scene and compares it against its reference image.
+// On failure, the actual and diff images are written to the output directory, and the failed scenes are listed in the error.
+pub fn run(options: &GoldenOptions) -> Result<(), String> {
+    let mut failures = Vec::new();
+
+    for scene in golden_scenes() {
+        let actual = render_scene(&scene, GOLDEN_WIDTH, GOLDEN_HEIGHT)?;
+        let reference_path = options.reference_dir.join(format!("{}.png", scene.name));
+
+        if options.update {
+            std::fs::create_dir_all(&options.reference_dir).map_err(|err| err.to_string())?;
+            screenshot::save_image(&reference_path, GOLDEN_WIDTH, GOLDEN_HEIGHT, &actual)
+                .map_err(|err| format!("Failed to save \"{}\": {err}", reference_path.display()))?;
+            println!("{}: updated {}", scene.name, reference_path.display());
+            continue;
+        }
+
+        let (width, height, expected) = screenshot::load_png(&reference_path)
+            .map_err(|err| format!("Failed to load \"{}\": {err}", reference_path.display()))?;
+        if (width, height) != (GOLDEN_WIDTH, GOLDEN_HEIGHT) {
+            failures.push(format!("{}: reference is {width}x{height}, expected {GOLDEN_WIDTH}x{GOLDEN_HEIGHT}", scene.name));
+            continue;
+        }
+
+        let comparison = compare_images(width, height, &expected, &actual, &options.tolerance);
+        let summary = format!(
+            "{}: {} of {} pixels differ (max difference {}), SSIM {:.4}",
+            scene.name, comparison.differing_pixels, comparison.total_pixels, comparison.max_difference, comparison.ssim
+        );
+        if comparison.passes(&options.tolerance) {
+            println!("{summary}");
+            continue;
+        }
+
+        // Write the actual and diff images so the failure can be inspected
+        std::fs::create_dir_all(&options.output_dir).map_err(|err| err.to_string())?;
+        let actual_path = options.output_dir.join(format!("{}.actual.png", scene.name));
+        let diff_path = options.output_dir.join(format!("{}.diff.png", scene.name));
+        screenshot::save_image(&actual_path, width, height, &actual).map_err(|err| err.to_string())?;
+        screenshot::save_image(&diff_path, width, height, &comparison.diff_image).map_err(|err| err.to_string())?;
+        failures.push(format!("{summary}, see {}", diff_path.display()));
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+    return Err(failures.join("\n"));
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::mesh::{Mesh, Model};
+use crate::texture::Texture;
+
+pub type ModelHandle = Handle<Model>;
+pub type MeshHandle = Handle<Mesh>;
+pub type TextureHandle = Handle<Texture>;
+
+// Every pool gets a unique id, so handles from one renderer can't be used to look up resources in another
+static NEXT_POOL_ID: AtomicU32 = AtomicU32::new(0);
+
+// Typed reference to a resource in a ResourcePool. The generation is bumped whenever a slot is freed,
+// so a handle to a removed resource is detected instead of silently pointing at whatever replaced it.
+pub struct Handle<T> {
+    pool_id: u32,
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+// Implemented by hand, because deriving these would require T to implement them too
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pool_id == other.pool_id && self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pool_id.hash(state);
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap_or("");
+        write!(f, "{type_name}Handle({}v{} in pool {})", self.index, self.generation, self.pool_id)
+    }
+}
+
+impl<T> Handle<T> {
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    Stale,   // The resource this handle pointed to has been removed
+    Foreign, // The handle was created by a different pool, for example another renderer
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandleError::Stale => write!(f, "handle refers to a resource that has been removed"),
+            HandleError::Foreign => write!(f, "handle belongs to a different renderer"),
+        }
+    }
+}
+
+impl std::error::Error for HandleError {}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+// Storage for renderer resources. T is what's stored, K is the kind of handle that refers to it,
+// for example the Metal renderer stores metal::Texture but hands out TextureHandles.
+pub struct ResourcePool<T, K = T> {
+    id: u32,
+    slots: Vec<Slot<T>>,
+    free_slots: Vec<u32>,
+    _marker: PhantomData<fn() -> K>,
+}
+
+impl<T, K> ResourcePool<T, K> {
+    pub fn new() -> Self {
+        ResourcePool {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> Handle<K> {
+        // Reuse a free slot if there is one, its generation was already bumped when it was freed
+        let index = match self.free_slots.pop() {
+            Some(index) => {
+                self.slots[index as usize].value = Some(value);
+                index
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        Handle {
+            pool_id: self.id,
+            index,
+            generation: self.slots[index as usize].generation,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self, handle: Handle<K>) -> Result<&T, HandleError> {
+        let slot = self.slot(handle)?;
+        slot.value.as_ref().ok_or(HandleError::Stale)
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<K>) -> Result<&mut T, HandleError> {
+        self.slot(handle)?;
+        self.slots[handle.index as usize].value.as_mut().ok_or(HandleError::Stale)
+    }
+
+    pub fn remove(&mut self, handle: Handle<K>) -> Result<T, HandleError> {
+        self.slot(handle)?;
+        let slot = &mut self.slots[handle.index as usize];
+        let value = slot.value.take().ok_or(HandleError::Stale)?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index);
+        Ok(value)
+    }
+
+    pub fn contains(&self, handle: Handle<K>) -> bool {
+        self.get(handle).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, handle: Handle<K>) -> Result<&Slot<T>, HandleError> {
+        if handle.pool_id != self.id {
+            return Err(HandleError::Foreign);
+        }
+        let slot = self.slots.get(handle.index as usize).ok_or(HandleError::Foreign)?;
+        if slot.generation != handle.generation {
+            return Err(HandleError::Stale);
+        }
+        Ok(slot)
+    }
+}
+
+impl<T, K> Default for ResourcePool<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use glam::{Quat, Vec3};
+
+use crate::error::AssetError;
+use crate::graphics::{ModelQueueEntry, RenderBackend};
+use crate::renderer_software::SoftwareRenderer;
+use crate::screenshot;
+use crate::structs::Transform;
+
+pub struct HeadlessOptions {
+    pub output_path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub software: bool, // Use the software renderer, even when Metal is available
+}
+
+impl HeadlessOptions {
+    // Parses `--headless <output.png|output.ppm> [--size <width>x<height>] [--software]`.
+    // Returns Ok(None) when --headless was not passed, so the caller can open the viewer instead.
+    pub fn from_args(args: &[String]) -> Result<Option<HeadlessOptions>, String> {
+        let mut options = HeadlessOptions {
+            output_path: PathBuf::new(),
+            width: 1280,
+            height: 720,
+            software: false,
+        };
+        let mut headless = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {
+                    headless = true;
+                    options.output_path = PathBuf::from(args.next().ok_or("--headless expects an output path")?);
+                }
+                "--size" => {
+                    let size = args.next().ok_or("--size expects <width>x<height>")?;
+                    let (width, height) = size.split_once('x').ok_or(format!("Invalid size \"{size}\", expected <width>x<height>"))?;
+                    options.width = width.parse().map_err(|_| format!("Invalid width \"{width}\""))?;
+                    options.height = height.parse().map_err(|_| format!("Invalid height \"{height}\""))?;
+                }
+                "--software" => options.software = true,
+                _ => return Err(format!("Unknown argument \"{arg}\"")),
+            }
+        }
+
+        if !headless {
+            return Ok(None);
+        }
+        if options.width == 0 || options.height == 0 {
+            return Err("The output size can not be zero".to_string());
+        }
+        return Ok(Some(options));
+    }
+}
+
+// Creates a renderer that draws into memory instead of a window. Uses Metal when available, unless software is requested.
+#[cfg_attr(not(target_os = "macos"), allow(unused_variables))]
+// Fails when the Metal shaders don't compile.
+pub fn create_renderer(width: u32, height: u32, software: bool) -> Result<Box<dyn RenderBackend>, AssetError> {
+    #[cfg(target_os = "macos")]
+    if !software {
+        let mut renderer = crate::renderer_metal::MetalRenderer::new_headless(width, height)?;
+        renderer.prepare_pipeline_state("hello_triangle_vertex", "hello_triangle_fragment");
+        return Ok(Box::new(renderer));
+    }
+
+    let mut renderer = SoftwareRenderer::new(width, height);
+    renderer.prepare_pipeline_state("hello_triangle_vertex", "hello_triangle_fragment");
+    return Ok(Box::new(renderer));
+}
+
+// Renders a single frame with the given draw queue, and saves it to disk.
+// Pass None as the camera to keep the one the renderer already has, for example one set with use_gltf_camera.
+pub fn render_to_file(renderer: &mut dyn RenderBackend, camera: Option<&Transform>, model_queue: Vec<ModelQueueEntry>, path: &Path) -> std::io::Result<()> {
+    if let Some(camera) = camera {
+        renderer.update_camera(camera);
+    }
+    renderer.begin_frame();
+    for entry in model_queue {
+        renderer.draw_model(entry);
+    }
+    renderer.end_frame();
+    screenshot::save_framebuffer(renderer, path)
+}
+
+// Renders the same scene as the viewer (suzanne and the sub nivis gun) to an image file
+pub fn run(options: &HeadlessOptions) -> Result<(), String> {
+    let mut renderer = create_renderer(options.width, options.height, options.software).map_err(|err| err.to_string())?;
+
+    let model_suzanne = renderer.load_model(Path::new("./assets/suzanne.gltf")).map_err(|err| err.to_string())?;
+    let model_gun = renderer.load_model(Path::new("./assets/sub_nivis_gun.gltf")).map_err(|err| err.to_string())?;
+
+    let camera = Transform {
+        translation: Vec3 {x: 0.0, y: 0.0, z: 0.5},
+        rotation: Quat::IDENTITY,
+        scale: Vec3 {x: 1.0, y: 1.0, z: 1.0},
+    };
+    let model_queue = vec![
+        ModelQueueEntry{
+            model: model_gun,
+            transform: Transform {
+                translation: Vec3{x: 0.0, y: 0.0, z: 0.0},
+                rotation: Quat::IDENTITY,
+                scale: Vec3{x: 1.0, y: 1.0, z: 1.0} }
+        },
+        ModelQueueEntry{
+            model: model_suzanne,
+            transform: Transform {
+                translation: Vec3{x: 0.5, y: 0.0, z: 0.0},
+                rotation: Quat::IDENTITY,
+                scale: Vec3{x: 0.1, y: 0.1, z: 0.1} }
+        },
+    ];
+
+    // Look through the first camera in the scene, if one of the models has one
+    let has_gltf_camera = model_queue.iter().any(|entry| matches!(renderer.use_gltf_camera(entry.model, 0, &entry.transform), Ok(true)));
+    let camera = if has_gltf_camera { None } else { Some(&camera) };
+
+    render_to_file(renderer.as_mut(), camera, model_queue, &options.output_path)
+        .map_err(|err| format!("Failed to save \"{}\": {err}", options.output_path.display()))?;
+    println!("Saved frame to {}", options.output_path.display());
+    return Ok(());
+}
@@ -1,4 +1,4 @@
-use glam::Vec2;
+use glam::{Mat4, Vec2, Vec4};
 
 pub fn index_to_coords(index: usize, width: usize) -> glam::Vec2 {
     glam::vec2((index % width) as f32, (index / width) as f32)
@@ -17,13 +17,34 @@ pub fn to_argb8(a: u8, r: u8, g: u8, b: u8) -> u32 {
     (a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | (b as u32)
 }
 
+// Texture and framebuffer pixels are stored as RGBA8, with red in the lowest byte
+pub fn unpack_color(pixel: u32) -> Vec4 {
+    let bytes = pixel.to_le_bytes();
+    Vec4::new(bytes[0] as f32, bytes[1] as f32, bytes[2] as f32, bytes[3] as f32) / 255.0
+}
+
+pub fn pack_color(color: Vec4) -> u32 {
+    let bytes = (color.clamp(Vec4::ZERO, Vec4::ONE) * 255.0).round();
+    u32::from_le_bytes([bytes.x as u8, bytes.y as u8, bytes.z as u8, bytes.w as u8])
+}
+
+// Transforms normals so they stay perpendicular to the surface when the model matrix scales non-uniformly.
+// A matrix that flattens the mesh has no inverse, then the model matrix itself is used.
+pub fn normal_matrix(model_matrix: Mat4) -> Mat4 {
+    if model_matrix.determinant() == 0.0 {
+        model_matrix
+    } else {
+        model_matrix.inverse().transpose()
+    }
+}
+
 pub fn edge_function(v0: Vec2, v1: Vec2, p: Vec2) -> f32 {
     let v0_p = p - v0;
     let v0_v1 = v1 - v0;
     (v0_p.x * v0_v1.y) - (v0_p.y * v0_v1.x)
 }
 
-fn point_inside_triangle(v0: Vec2, v1: Vec2, v2: Vec2, p: Vec2) -> bool {
+pub fn point_inside_triangle(v0: Vec2, v1: Vec2, v2: Vec2, p: Vec2) -> bool {
     (edge_function(v0, v1, p) > 0.0)
         && (edge_function(v1, v2, p) > 0.0)
         && (edge_function(v2, v0, p) > 0.0)
@@ -0,0 +1,103 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use gltf::{buffer, image, Document, Gltf};
+
+use crate::accessor::view_bytes;
+use crate::error::AssetError;
+
+// Reads the file an external URI points to, relative to wherever the glTF file came from.
+// URIs are percent-decoded before they're passed in, so "my%20mesh.bin" becomes "my mesh.bin".
+pub type Resolver<'a> = dyn FnMut(&str) -> io::Result<Vec<u8>> + 'a;
+
+// Parses a glTF JSON or GLB file, and reads its buffers and images. Embedded (data URI and GLB) data is read from the file itself,
+// everything else goes through the resolver. The path is only used in error messages.
+pub(crate) fn import_slice(
+    bytes: &[u8],
+    path: &Path,
+    resolver: &mut Resolver,
+) -> Result<(Document, Vec<buffer::Data>, Vec<image::Data>), AssetError> {
+    let Gltf { document, mut blob } = Gltf::from_slice(bytes).map_err(|err| AssetError::from_gltf(path.to_path_buf(), err))?;
+
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let mut data = match buffer.source() {
+            buffer::Source::Bin => blob.take().ok_or(AssetError::MalformedData(format!("buffer {} uses a missing GLB blob", buffer.index())))?,
+            buffer::Source::Uri(uri) => read_uri(uri, resolver)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(AssetError::MalformedData(format!(
+                "buffer {} should be {} bytes, but is only {}",
+                buffer.index(),
+                buffer.length(),
+                data.len()
+            )));
+        }
+        // Accessors assume buffers are padded to 4 bytes
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        buffers.push(buffer::Data(data));
+    }
+
+    let mut images = Vec::new();
+    for image in document.images() {
+        let encoded = match image.source() {
+            image::Source::View { view, .. } => view_bytes(&view, &buffers)?.to_vec(),
+            image::Source::Uri { uri, .. } => read_uri(uri, resolver)?,
+        };
+        images.push(decode_image(&encoded, image.index(), path)?);
+    }
+    Ok((document, buffers, images))
+}
+
+// Data URIs are decoded here, relative and file: URIs go to the resolver
+fn read_uri(uri: &str, resolver: &mut Resolver) -> Result<Vec<u8>, AssetError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let Some((_, base64)) = data.split_once(";base64,") else {
+            return Err(AssetError::UnsupportedFormat(format!("data URI \"{}\" is not base64", truncate(uri))));
+        };
+        return base64::decode(base64).map_err(|err| AssetError::MalformedData(format!("data URI \"{}\": {err}", truncate(uri))));
+    }
+
+    let file = match uri.strip_prefix("file://").or(uri.strip_prefix("file:")) {
+        Some(file) => file,
+        None if uri.contains(':') => return Err(AssetError::UnsupportedFormat(format!("URI scheme of \"{uri}\""))),
+        None => uri,
+    };
+    let file = urlencoding::decode(file).map_err(|err| AssetError::MalformedData(format!("URI \"{uri}\": {err}")))?;
+    return resolver(&file).map_err(|source| AssetError::Io { path: PathBuf::from(file.as_ref()), source });
+}
+
+// Base64 data can be megabytes long, which is no use in an error message
+fn truncate(uri: &str) -> String {
+    match uri.char_indices().nth(48) {
+        Some((end, _)) => format!("{}...", &uri[..end]),
+        None => uri.to_string(),
+    }
+}
+
+// Decodes a PNG or JPEG into the 8-bit format the glTF crate uses, so it can go through Texture::load_texture_from_gltf_image
+fn decode_image(encoded: &[u8], index: usize, path: &Path) -> Result<image::Data, AssetError> {
+    let image = match stb_image::image::load_from_memory(encoded) {
+        stb_image::image::LoadResult::ImageU8(image) => image,
+        stb_image::image::LoadResult::ImageF32(_) => {
+            return Err(AssetError::UnsupportedFormat(format!("image {index} is an HDR image")));
+        }
+        stb_image::image::LoadResult::Error(message) => {
+            return Err(AssetError::Parse { path: path.to_path_buf(), message: format!("image {index}: {message}") });
+        }
+    };
+    let format = match image.depth {
+        1 => image::Format::R8,
+        2 => image::Format::R8G8,
+        3 => image::Format::R8G8B8,
+        _ => image::Format::R8G8B8A8,
+    };
+    Ok(image::Data {
+        pixels: image.data,
+        format,
+        width: image.width as u32,
+        height: image.height as u32,
+    })
+}
@@ -0,0 +1,44 @@
+#![allow(clippy::needless_return)]
+
+pub mod animation;
+pub mod camera;
+pub mod material;
+mod accessor;
+mod import;
+pub mod mesh;
+pub mod light;
+pub mod morph;
+pub mod pbr;
+pub mod sampling;
+pub mod scene;
+pub mod skin;
+mod tangents;
+pub mod texture;
+pub mod structs;
+pub mod helpers;
+pub mod error;
+pub mod graphics;
+pub mod handle;
+pub mod renderer_software;
+pub mod screenshot;
+pub mod headless;
+pub mod golden;
+#[cfg(target_os = "macos")]
+pub mod renderer_metal;
+
+pub use animation::{Animation, AnimationPlayer};
+pub use camera::{Camera, Projection};
+pub use error::AssetError;
+pub use graphics::{ModelQueueEntry, RenderBackend, Renderer};
+pub use light::{Light, LightKind, PlacedLight};
+pub use handle::{HandleError, MeshHandle, ModelHandle, TextureHandle};
+pub use import::Resolver;
+pub use material::{AlphaMode, Material, MaterialTexture};
+pub use mesh::{LoadOptions, Mesh, Model};
+pub use scene::{Node, Scene};
+pub use skin::Skin;
+pub use renderer_software::SoftwareRenderer;
+pub use structs::Transform;
+pub use texture::{Sampler, Texture};
+#[cfg(target_os = "macos")]
+pub use renderer_metal::MetalRenderer;
@@ -0,0 +1,134 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::mesh::Model;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional, // Infinitely far away, shines along the node's -Z axis. Intensity is in lux.
+    Point,       // Shines in every direction from the node's position. Intensity is in candela.
+    Spot {
+        inner_cone_angle: f32, // Radians from the center of the cone where the light starts to fall off
+        outer_cone_angle: f32, // Radians from the center of the cone where the light is gone
+    },
+}
+
+// A light from the KHR_lights_punctual extension. Nodes refer to it by index, and the node's transform places it in the scene.
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub name: String,
+    pub kind: LightKind,
+    pub color: Vec3, // Linear RGB
+    pub intensity: f32,
+    pub range: Option<f32>, // Distance where the light stops having an effect, None means infinite
+}
+
+// A light placed in the world, ready to shade with
+#[derive(Debug, Clone)]
+pub struct PlacedLight {
+    pub light: Light,
+    pub position: Vec3,  // Ignored by directional lights
+    pub direction: Vec3, // Direction the light shines in, ignored by point lights
+}
+
+// The layout of a light in the GPU light buffer, matches light_t in hello_triangle.metal
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
+pub struct LightData {
+    pub position: Vec4,  // w is the range, 0 when the light has an infinite range
+    pub direction: Vec4, // w is unused
+    pub color: Vec4,     // Color times intensity, w is unused
+    pub kind: u32,       // 0 = directional, 1 = point, 2 = spot
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    pub _padding: u32,
+}
+
+impl PlacedLight {
+    pub fn to_light_data(&self) -> LightData {
+        let (kind, inner_cone_cos, outer_cone_cos) = match self.light.kind {
+            LightKind::Directional => (0, 0.0, 0.0),
+            LightKind::Point => (1, 0.0, 0.0),
+            LightKind::Spot { inner_cone_angle, outer_cone_angle } => (2, inner_cone_angle.cos(), outer_cone_angle.cos()),
+        };
+        LightData {
+            position: self.position.extend(self.light.range.unwrap_or(0.0)),
+            direction: self.direction.extend(0.0),
+            color: (self.light.color * self.light.intensity).extend(0.0),
+            kind,
+            inner_cone_cos,
+            outer_cone_cos,
+            _padding: 0,
+        }
+    }
+
+    // The light that reaches a surface point, before the surface's BRDF and the cosine term. Also returns the direction towards the light.
+    pub fn incoming_light(&self, position: Vec3) -> (Vec3, Vec3) {
+        let radiance = self.light.color * self.light.intensity;
+        if self.light.kind == LightKind::Directional {
+            return (radiance, -self.direction.normalize());
+        }
+
+        // Inverse square falloff, smoothly cut off at the range like the KHR_lights_punctual spec recommends
+        let to_light = self.position - position;
+        let distance_squared = to_light.length_squared().max(0.0001);
+        let to_light = to_light / distance_squared.sqrt();
+        let mut attenuation = 1.0 / distance_squared;
+        if let Some(range) = self.light.range {
+            let window = (1.0 - (distance_squared / (range * range)).powi(2)).clamp(0.0, 1.0);
+            attenuation *= window * window;
+        }
+
+        // Spot lights fade out between the inner and outer cone
+        if let LightKind::Spot { inner_cone_angle, outer_cone_angle } = self.light.kind {
+            let scale = 1.0 / (inner_cone_angle.cos() - outer_cone_angle.cos()).max(0.001);
+            let offset = -outer_cone_angle.cos() * scale;
+            let cone = (self.direction.normalize().dot(-to_light) * scale + offset).clamp(0.0, 1.0);
+            attenuation *= cone * cone;
+        }
+        return (radiance * attenuation, to_light);
+    }
+}
+
+impl Model {
+    // Every light in the scene, placed in the world with the given model matrix. Make sure the world matrices are up to date first.
+    pub fn placed_lights(&self, model_matrix: Mat4) -> Vec<PlacedLight> {
+        let mut result = Vec::new();
+        for node_index in self.scene_nodes() {
+            let Some(light) = self.nodes[node_index].light.and_then(|light| self.lights.get(light)) else {
+                continue;
+            };
+            let matrix = model_matrix * self.nodes[node_index].world_matrix;
+            result.push(PlacedLight {
+                light: light.clone(),
+                position: matrix.transform_point3(Vec3::ZERO),
+                direction: matrix.transform_vector3(-Vec3::Z).normalize_or_zero(),
+            });
+        }
+        return result;
+    }
+
+    pub fn find_light(&self, name: &str) -> Option<usize> {
+        self.lights.iter().position(|light| light.name == name)
+    }
+}
+
+pub(crate) fn load_lights(document: &gltf::Document) -> Vec<Light> {
+    let Some(lights) = document.lights() else {
+        return Vec::new();
+    };
+    lights
+        .map(|light| Light {
+            name: String::from(light.name().unwrap_or("untitled")),
+            kind: match light.kind() {
+                gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+                gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+                gltf::khr_lights_punctual::Kind::Spot { inner_cone_angle, outer_cone_angle } => {
+                    LightKind::Spot { inner_cone_angle, outer_cone_angle }
+                }
+            },
+            color: Vec3::from(light.color()),
+            intensity: light.intensity(),
+            range: light.range(),
+        })
+        .collect()
+}
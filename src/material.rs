@@ -1,29 +1,228 @@
-use glam::Vec3;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use glam::{UVec4, Vec3, Vec4};
+
+use crate::error::AssetError;
+use crate::graphics::RenderBackend;
+use crate::handle::TextureHandle;
+use crate::mesh::Model;
+use crate::texture::{Sampler, Texture};
+
+// How the alpha channel of the base color is used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,    // Alpha is ignored
+    Mask(f32), // Pixels with an alpha below the cutoff are discarded, the rest is opaque
+    Blend,     // Alpha blends the surface with whatever is behind it
+}
+
+// A texture a material samples, along with the UV set and sampler it samples it with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialTexture {
+    pub texture: TextureHandle,
+    pub tex_coord: u32, // 0 samples with Vertex::uv0, 1 with Vertex::uv1. Vertices don't have more UV sets, higher ones use uv0.
+    pub sampler: Sampler, // Samplers belong to the glTF texture, so slots sharing an image can still sample it differently
+}
+
+// The glTF metallic-roughness material. Texture values are multiplied with the matching factor.
 #[derive(Debug, Clone)]
 pub struct Material {
-    // Textures - indices to renderer's texture array
-    pub tex_alb: i32,
-    pub tex_nrm: i32,
-    pub tex_mtl_rgh: i32,
-    pub tex_emm: i32,
+    pub name: String, // Only for finding the material, primitives refer to materials by index
+
+    // Textures, None if the material doesn't have one
+    pub tex_alb: Option<MaterialTexture>,     // Base color in sRGB, alpha in the alpha channel
+    pub tex_nrm: Option<MaterialTexture>,     // Tangent space normal map
+    pub tex_mtl_rgh: Option<MaterialTexture>, // Roughness in the green channel, metallic in the blue channel
+    pub tex_occ: Option<MaterialTexture>,     // Ambient occlusion in the red channel
+    pub tex_emm: Option<MaterialTexture>,     // Emissive color in sRGB
 
     // Scalars
+    pub scl_alb: Vec4, // Linear RGBA
     pub scl_rgh: f32,
     pub scl_mtl: f32,
+    pub scl_nrm: f32, // Scales the X and Y of the normal map
+    pub scl_occ: f32, // How much of the occlusion map is applied, 0 is none at all
     pub scl_emm: Vec3,
+
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool, // Back faces are drawn and lit with a flipped normal, instead of being culled
+}
+
+// The layout of a material in the GPU material buffer, matches material_t in hello_triangle.metal
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct MaterialData {
+    pub base_color: Vec4,
+    pub emissive: Vec4, // w is unused
+    pub metallic: f32,
+    pub roughness: f32,
+    pub normal_scale: f32, // 0 when there is no normal map, which leaves the vertex normal alone
+    pub occlusion_strength: f32,
+    pub tex_coords: UVec4, // UV set of the base color, metallic/roughness, normal and occlusion textures
+    pub tex_coord_emm: u32, // UV set of the emissive texture
+    pub alpha_mode: u32,    // 0 = opaque, 1 = mask, 2 = blend
+    pub alpha_cutoff: f32,  // Only used by masked materials
+    pub double_sided: u32,  // 1 when back faces are drawn, with a flipped normal
 }
 
 impl Material {
+    // The glTF default material: a white, fully metallic and fully rough surface without textures
     pub fn new() -> Self {
         Material {
-            tex_alb: -1,
-            tex_nrm: -1,
-            tex_mtl_rgh: -1,
-            tex_emm: -1,
-            scl_rgh: 0.0,
-            scl_mtl: 0.0,
+            name: String::from("default"),
+            tex_alb: None,
+            tex_nrm: None,
+            tex_mtl_rgh: None,
+            tex_occ: None,
+            tex_emm: None,
+            scl_alb: Vec4::ONE,
+            scl_rgh: 1.0,
+            scl_mtl: 1.0,
+            scl_nrm: 1.0,
+            scl_occ: 1.0,
             scl_emm: Vec3::ZERO,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+
+    // The factors and UV sets of the material, ready to upload. Missing textures are bound as white.
+    pub fn to_material_data(&self) -> MaterialData {
+        let tex_coord = |slot: Option<MaterialTexture>| slot.map_or(0, |slot| slot.tex_coord);
+        let (alpha_mode, alpha_cutoff) = match self.alpha_mode {
+            AlphaMode::Opaque => (0, 0.0),
+            AlphaMode::Mask(cutoff) => (1, cutoff),
+            AlphaMode::Blend => (2, 0.0),
+        };
+        MaterialData {
+            base_color: self.scl_alb,
+            emissive: self.scl_emm.extend(0.0),
+            metallic: self.scl_mtl,
+            roughness: self.scl_rgh,
+            normal_scale: if self.tex_nrm.is_some() { self.scl_nrm } else { 0.0 },
+            occlusion_strength: self.scl_occ,
+            tex_coords: UVec4::new(tex_coord(self.tex_alb), tex_coord(self.tex_mtl_rgh), tex_coord(self.tex_nrm), tex_coord(self.tex_occ)),
+            tex_coord_emm: tex_coord(self.tex_emm),
+            alpha_mode,
+            alpha_cutoff,
+            double_sided: self.double_sided as u32,
+        }
+    }
+
+    // Every texture slot, in the order the renderers bind them: base color, metallic/roughness, normal, occlusion and emissive
+    pub fn texture_slots(&self) -> [Option<MaterialTexture>; 5] {
+        [self.tex_alb, self.tex_mtl_rgh, self.tex_nrm, self.tex_occ, self.tex_emm]
+    }
+
+    // All the textures this material uses. Materials can share textures, see Model::textures.
+    pub fn textures(&self) -> impl Iterator<Item = TextureHandle> {
+        [self.tex_alb, self.tex_nrm, self.tex_mtl_rgh, self.tex_occ, self.tex_emm]
+            .into_iter()
+            .flatten()
+            .map(|slot| slot.texture)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Model {
+    // The material a primitive is drawn with, given its Primitive::material
+    pub fn material(&self, material: Option<usize>) -> &Material {
+        material.and_then(|index| self.materials.get(index)).unwrap_or(&self.default_material)
+    }
+
+    // Names don't have to be unique, this finds the first material with the name
+    pub fn find_material(&self, name: &str) -> Option<usize> {
+        self.materials.iter().position(|material| material.name == name)
+    }
+
+    // Every texture the materials of this model use, once, so they can be freed together with it
+    pub fn textures(&self) -> HashSet<TextureHandle> {
+        self.materials.iter().flat_map(Material::textures).collect()
+    }
+}
+
+pub(crate) fn load_materials<B: RenderBackend + ?Sized>(
+    document: &gltf::Document,
+    image_data: &[gltf::image::Data],
+    renderer: &mut B,
+) -> Result<Vec<Material>, AssetError> {
+    // Every image is decoded before anything is uploaded, so a broken image doesn't leave the others in the renderer without an owner
+    let mut decoded = BTreeMap::new();
+    for material in document.materials() {
+        for texture in material_textures(&material) {
+            let index = texture.source().index();
+            if let Entry::Vacant(entry) = decoded.entry(index) {
+                let image = image_data.get(index).ok_or(AssetError::MalformedData(format!("image {index} does not exist")))?;
+                entry.insert(Texture::load_texture_from_gltf_image(image)?);
+            }
+        }
+    }
+
+    // Images are uploaded once, even when several slots or materials use them, like a combined occlusion/roughness/metallic map
+    let uploaded: HashMap<usize, TextureHandle> =
+        decoded.into_iter().map(|(index, mut texture)| (index, renderer.upload_texture(&mut texture))).collect();
+    let slot = |texture: gltf::Texture, tex_coord: u32| MaterialTexture {
+        texture: uploaded[&texture.source().index()],
+        tex_coord,
+        sampler: Sampler::from_gltf(&texture.sampler()),
+    };
+
+    let mut materials = Vec::new();
+    for material in document.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        let mut new_material = Material::new();
+        new_material.name = String::from(material.name().unwrap_or("untitled"));
+
+        // Get PBR parameters
+        new_material.scl_alb = pbr.base_color_factor().into();
+        new_material.scl_rgh = pbr.roughness_factor();
+        new_material.scl_mtl = pbr.metallic_factor();
+        new_material.scl_emm = material.emissive_factor().into();
+        new_material.alpha_mode = match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5)),
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        };
+        new_material.double_sided = material.double_sided();
+
+        // Get the textures
+        if let Some(info) = pbr.base_color_texture() {
+            new_material.tex_alb = Some(slot(info.texture(), info.tex_coord()));
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            new_material.tex_mtl_rgh = Some(slot(info.texture(), info.tex_coord()));
+        }
+        if let Some(info) = material.normal_texture() {
+            new_material.tex_nrm = Some(slot(info.texture(), info.tex_coord()));
+            new_material.scl_nrm = info.scale();
         }
+        if let Some(info) = material.occlusion_texture() {
+            new_material.tex_occ = Some(slot(info.texture(), info.tex_coord()));
+            new_material.scl_occ = info.strength();
+        }
+        if let Some(info) = material.emissive_texture() {
+            new_material.tex_emm = Some(slot(info.texture(), info.tex_coord()));
+        }
+
+        materials.push(new_material);
     }
+    Ok(materials)
+}
+
+// Every texture a glTF material uses, in any slot
+fn material_textures<'a>(material: &gltf::Material<'a>) -> Vec<gltf::Texture<'a>> {
+    let pbr = material.pbr_metallic_roughness();
+    let mut textures = Vec::new();
+    textures.extend(pbr.base_color_texture().map(|info| info.texture()));
+    textures.extend(pbr.metallic_roughness_texture().map(|info| info.texture()));
+    textures.extend(material.normal_texture().map(|info| info.texture()));
+    textures.extend(material.occlusion_texture().map(|info| info.texture()));
+    textures.extend(material.emissive_texture().map(|info| info.texture()));
+    textures
 }
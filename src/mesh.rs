@@ -1,177 +1,415 @@
-use crate::graphics::Renderer;
-use crate::material::Material;
-use crate::structs::Transform;
+use crate::accessor::{read_accessor_f32, read_accessor_u32};
+use crate::animation::{load_animations, Animation};
+use crate::camera::{load_cameras, Camera};
+use crate::error::AssetError;
+use crate::graphics::RenderBackend;
+use crate::handle::MeshHandle;
+use crate::import::{import_slice, Resolver};
+use crate::light::{load_lights, Light};
+use crate::material::{load_materials, Material};
+use crate::morph::{load_morph_targets, MorphTarget};
+use crate::scene::{load_nodes, load_scenes, Node, Scene};
+use crate::skin::{check_joint_indices, load_skins, Skin};
 use crate::structs::Vertex;
-use crate::texture::Texture;
-use glam::Vec4Swizzles;
-use glam::{Mat4, Vec2, Vec3, Vec4};
+use glam::{UVec4, Vec2, Vec3, Vec4};
 use gltf::buffer::Data;
-use metal::Buffer;
-use std::{collections::HashMap, path::Path};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Index buffer of a mesh. 16-bit indices are used whenever the vertex count allows it, to save memory.
+#[derive(Debug, Clone)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+// How the indices of a mesh are put together. glTF strips, loops and fans are converted to one of these when loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimitiveType {
+    Points,    // Every index is a point
+    Lines,     // Every 2 indices form a line
+    Triangles, // Every 3 indices form a triangle
+}
+
+// How faces that share a vertex add up to its normal, when smoothing normals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    Area,  // Bigger faces count more
+    Angle, // Faces count by the angle of their corner at the vertex, so the result doesn't depend on how faces are split up
+}
+
+// How normals are generated for primitives that don't have them
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NormalMode {
+    #[default]
+    Flat, // Every triangle gets its own face normal, which is what the glTF spec asks for
+    Smooth {
+        hard_edge_angle: f32, // Faces that meet at a bigger angle than this (in radians) keep a hard edge between them
+        weighting: NormalWeighting,
+    },
+}
 
 pub struct Mesh {
     pub verts: Vec<Vertex>,
-    pub buffer: Option<Buffer>,
+    pub indices: Indices,
+    pub primitive_type: PrimitiveType,
+    pub morph_targets: Vec<MorphTarget>, // Blended with the weights of the node that draws the mesh
+    pub buffer: Option<MeshHandle>, // Vertex and index buffer on the render backend, set once the mesh is uploaded
 }
 
-pub struct Model {
-    pub meshes: HashMap<String, Mesh>, // Where the String is the material id
-    pub materials: HashMap<String, Material>, // Where the String is the material id
-}
+impl Indices {
+    // Picks the smallest index type that can address every vertex
+    pub fn new(indices: Vec<u32>, vertex_count: usize) -> Indices {
+        if vertex_count <= u16::MAX as usize + 1 {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
 
-// So what this function needs to do: &[u8] -(reinterpret)> &[SrcCompType] -(convert)> &[DstCompType]
-fn reinterpret_then_convert<SrcCompType, DstCompType>(input_buffer: &[u8]) -> Vec<DstCompType>
-where
-    DstCompType: From<SrcCompType>,
-    SrcCompType: Copy,
-{
-    // &[u8] -> &[SrcCompType]
-    let input_ptr = input_buffer.as_ptr();
-    let src_comp_buffer: &[SrcCompType] = unsafe {
-        std::slice::from_raw_parts(
-            std::mem::transmute(input_ptr),
-            input_buffer.len() / std::mem::size_of::<SrcCompType>(),
-        )
-    };
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-    // &[SrcCompType] -> Vec<DstCompType>
-    let mut dst_comp_vec = Vec::<DstCompType>::new();
-    for item in src_comp_buffer {
-        dst_comp_vec.push(DstCompType::from(*item));
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            Indices::U16(indices) => indices[i] as u32,
+            Indices::U32(indices) => indices[i],
+        }
     }
 
-    // Return
-    dst_comp_vec
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        self.iter().collect()
+    }
+
+    // Size of a single index in bytes
+    pub fn stride(&self) -> usize {
+        match self {
+            Indices::U16(_) => 2,
+            Indices::U32(_) => 4,
+        }
+    }
+
+    // Pointer to the raw index data, for uploading it to the GPU
+    pub fn as_ptr(&self) -> *const u8 {
+        match self {
+            Indices::U16(indices) => indices.as_ptr() as *const u8,
+            Indices::U32(indices) => indices.as_ptr() as *const u8,
+        }
+    }
 }
 
-fn convert_gltf_buffer_to_f32(input_buffer: &[u8], accessor: &gltf::Accessor) -> Vec<f32> {
-    // Convert based on data type
-    // First we make a f64 vector (this way we can do fancy generics magic and still convert u32 to f32)
-    let values64 = match accessor.data_type() {
-        gltf::accessor::DataType::I8 => reinterpret_then_convert::<i8, f64>(input_buffer),
-        gltf::accessor::DataType::U8 => reinterpret_then_convert::<u8, f64>(input_buffer),
-        gltf::accessor::DataType::I16 => reinterpret_then_convert::<i16, f64>(input_buffer),
-        gltf::accessor::DataType::U16 => reinterpret_then_convert::<u16, f64>(input_buffer),
-        gltf::accessor::DataType::U32 => reinterpret_then_convert::<u32, f64>(input_buffer),
-        gltf::accessor::DataType::F32 => reinterpret_then_convert::<f32, f64>(input_buffer),
-    };
+impl Mesh {
+    // A mesh without vertices or indices has nothing to draw, like a triangle list with fewer than 3 indices.
+    // Renderers don't upload these, since Metal can't create empty buffers.
+    pub fn is_empty(&self) -> bool {
+        return self.verts.is_empty() || self.indices.is_empty();
+    }
+
+    // Calculates the normals of a triangle mesh from its faces. Vertices whose faces end up with different normals,
+    // like the corners of a flat shaded cube, are split into one vertex per normal. Other meshes are left alone.
+    pub fn generate_normals(&mut self, mode: NormalMode) {
+        if self.primitive_type != PrimitiveType::Triangles {
+            return;
+        }
+        let indices = self.indices.to_u32();
+        let triangles: Vec<[usize; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize])
+            .collect();
+
+        // The length of the cross product is twice the area of the triangle
+        let face_cross: Vec<Vec3> = triangles
+            .iter()
+            .map(|[a, b, c]| (self.verts[*b].position - self.verts[*a].position).cross(self.verts[*c].position - self.verts[*a].position))
+            .collect();
+        let face_normals: Vec<Vec3> = face_cross.iter().map(|cross| cross.normalize_or_zero()).collect();
+
+        let corner_normals: Vec<Vec3> = match mode {
+            NormalMode::Flat => face_normals.iter().flat_map(|normal| [*normal; 3]).collect(),
+            NormalMode::Smooth { hard_edge_angle, weighting } => {
+                // Faces are smoothed by position rather than by index, so UV seams don't show up as hard edges
+                let position_key = |index: usize| vector_key(self.verts[index].position);
+                let mut faces_at_position: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+                for (face, triangle) in triangles.iter().enumerate() {
+                    for (corner, index) in triangle.iter().enumerate() {
+                        faces_at_position.entry(position_key(*index)).or_default().push((face, corner));
+                    }
+                }
+
+                let min_cos = hard_edge_angle.cos();
+                let mut normals = Vec::with_capacity(indices.len());
+                for (face, triangle) in triangles.iter().enumerate() {
+                    for index in triangle {
+                        let mut normal = Vec3::ZERO;
+                        for (other_face, other_corner) in &faces_at_position[&position_key(*index)] {
+                            if face_normals[face].dot(face_normals[*other_face]) < min_cos {
+                                continue;
+                            }
+                            let weight = match weighting {
+                                NormalWeighting::Area => face_cross[*other_face].length(),
+                                NormalWeighting::Angle => {
+                                    let corners = triangles[*other_face];
+                                    let position = self.verts[corners[*other_corner]].position;
+                                    let to_next = (self.verts[corners[(other_corner + 1) % 3]].position - position).normalize_or_zero();
+                                    let to_prev = (self.verts[corners[(other_corner + 2) % 3]].position - position).normalize_or_zero();
+                                    to_next.dot(to_prev).clamp(-1.0, 1.0).acos()
+                                }
+                            };
+                            normal += face_normals[*other_face] * weight;
+                        }
+                        normals.push(normal.try_normalize().unwrap_or(face_normals[face]));
+                    }
+                }
+                normals
+            }
+        };
+        let corner_keys: Vec<[u32; 3]> = corner_normals.iter().map(|normal| vector_key(*normal)).collect();
+        self.split_corners(&indices, &corner_keys, |vertex, corner| vertex.normal = corner_normals[corner]);
+    }
+
+    // Gives every triangle corner its own version of its vertex, which `apply` makes from the corner's index into `indices`.
+    // Corners with the same key get the same version. A vertex keeps its index for the first key its corners have,
+    // and a copy of it is added for every other key.
+    pub(crate) fn split_corners<K: Copy + Eq + Hash>(&mut self, indices: &[u32], corner_keys: &[K], apply: impl Fn(&mut Vertex, usize)) {
+        let mut first_key: Vec<Option<K>> = vec![None; self.verts.len()];
+        let mut copies: HashMap<(u32, K), u32> = HashMap::new();
+        let mut source: Vec<usize> = (0..self.verts.len()).collect();
+        let mut new_indices = Vec::with_capacity(indices.len());
+        for (corner, (index, key)) in indices.iter().zip(corner_keys).enumerate() {
+            let key = *key;
+            let vertex = &mut self.verts[*index as usize];
+            match first_key[*index as usize] {
+                None => {
+                    first_key[*index as usize] = Some(key);
+                    apply(vertex, corner);
+                    new_indices.push(*index);
+                }
+                Some(first) if first == key => new_indices.push(*index),
+                Some(_) => {
+                    let mut copy = *vertex;
+                    apply(&mut copy, corner);
+                    let copy_index = *copies.entry((*index, key)).or_insert_with(|| {
+                        self.verts.push(copy);
+                        source.push(*index as usize);
+                        self.verts.len() as u32 - 1
+                    });
+                    new_indices.push(copy_index);
+                }
+            }
+        }
 
-    // Then we convert that to a f32 vector - this feels cursed as heck but let's ignore that, it'll be fine!
-    let mut values32 = Vec::<f32>::new();
-    values32.resize(values64.len(), 0.0);
-    for i in 0..values32.len() {
-        values32[i] = values64[i] as f32;
+        // Split vertices keep the morph target deltas of the vertex they came from
+        for target in &mut self.morph_targets {
+            for deltas in [&mut target.positions, &mut target.normals, &mut target.tangents] {
+                if !deltas.is_empty() {
+                    *deltas = source.iter().map(|index| deltas[*index]).collect();
+                }
+            }
+        }
+        self.indices = Indices::new(new_indices, self.verts.len());
     }
+}
+
+// Exact key for a vector in a hash map. Adding 0 turns -0 into 0, so they count as the same value.
+fn vector_key(vector: Vec3) -> [u32; 3] {
+    vector.to_array().map(|value| (value + 0.0).to_bits())
+}
+
+// A single primitive of a glTF mesh, along with the material it's drawn with
+pub struct Primitive {
+    pub mesh: Mesh,
+    pub material: Option<usize>, // Index into Model::materials, None means Model::default_material
+}
+
+pub struct Model {
+    pub meshes: Vec<Vec<Primitive>>, // One list of primitives per glTF mesh, in the same order as the file
+    pub materials: Vec<Material>, // Every material in the file, in the same order as the file
+    pub default_material: Material, // Used by primitives without a material, see Model::material
+    pub nodes: Vec<Node>, // Every node in the file, in the same order as the file
+    pub root_nodes: Vec<usize>, // The nodes of the scene that is drawn
+    pub scenes: Vec<Scene>, // Every scene in the file, use Model::set_scene to draw another one
+    pub scene: Option<usize>, // Index into Model::scenes of the scene that is drawn
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+    pub cameras: Vec<Camera>, // Nodes place these in the scene, see Model::camera_transform
+    pub lights: Vec<Light>,   // From KHR_lights_punctual, nodes place these in the scene
+}
 
-    // Return
-    values32
+// Settings for how Model::load_gltf_with_options processes the file
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub regenerate_tangents: bool, // Generate tangents even for primitives that have them, instead of only the ones without
+    pub normals: NormalMode,       // How to generate normals for primitives without them
+}
+
+// What error messages call models that were loaded from memory instead of a file
+const MEMORY_PATH: &str = "<memory>";
+
+// Converts the indices of any glTF primitive mode to a point, line or triangle list.
+// The triangle strip and fan orders follow the glTF spec, so the winding order is preserved.
+fn convert_to_list(mode: gltf::mesh::Mode, indices: Vec<u32>) -> (PrimitiveType, Vec<u32>) {
+    use gltf::mesh::Mode;
+    let n = indices.len();
+    match mode {
+        Mode::Points => (PrimitiveType::Points, indices),
+        Mode::Lines => (PrimitiveType::Lines, indices[..n - n % 2].to_vec()),
+        Mode::LineStrip => (
+            PrimitiveType::Lines,
+            indices.windows(2).flatten().copied().collect(),
+        ),
+        Mode::LineLoop => {
+            let mut lines: Vec<u32> = indices.windows(2).flatten().copied().collect();
+            if n > 2 {
+                lines.extend([indices[n - 1], indices[0]]);
+            }
+            (PrimitiveType::Lines, lines)
+        }
+        Mode::Triangles => (PrimitiveType::Triangles, indices[..n - n % 3].to_vec()),
+        Mode::TriangleStrip => (
+            PrimitiveType::Triangles,
+            (0..n.saturating_sub(2))
+                .flat_map(|i| [indices[i], indices[i + 1 + i % 2], indices[i + 2 - i % 2]])
+                .collect(),
+        ),
+        Mode::TriangleFan => (
+            PrimitiveType::Triangles,
+            (0..n.saturating_sub(2))
+                .flat_map(|i| [indices[i + 1], indices[i + 2], indices[0]])
+                .collect(),
+        ),
+    }
 }
 
 fn create_vertex_array(
     primitive: &gltf::Primitive,
     mesh_data: &[Data],
-    local_matrix: Mat4,
-) -> Mesh {
+    options: &LoadOptions,
+) -> Result<Mesh, AssetError> {
     let mut position_vec = Vec::<Vec3>::new();
     let mut normal_vec = Vec::<Vec3>::new();
     let mut tangent_vec = Vec::<Vec4>::new();
     let mut color_vec = Vec::<Vec4>::new();
     let mut texcoord0_vec = Vec::<Vec2>::new();
     let mut texcoord1_vec = Vec::<Vec2>::new();
-    let mut indices = Vec::<u16>::new();
+    let mut joints_vec = Vec::<UVec4>::new();
+    let mut weights_vec = Vec::<Vec4>::new();
 
     // Loop over all the primitive attributes
     for (name, accessor) in primitive.attributes() {
-        // Get buffer view
-        let bufferview = accessor.view().unwrap();
-
-        // Find location in buffer
-        let buffer_index = bufferview.buffer().index();
-        let buffer_offset = bufferview.offset();
-        let buffer_end = bufferview.offset() + bufferview.length();
-
-        // Find location in buffer
-        let buffer_base = &mesh_data[buffer_index].0;
-        let buffer_slice = buffer_base.get(buffer_offset..buffer_end).unwrap();
-
         // Assign to the vectors
         match name.to_string().as_str() {
             "POSITION" => {
-                let values = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
+                let values = read_accessor_f32(&accessor, mesh_data)?;
                 for i in (0..accessor.count() * 3).step_by(3) {
                     let slice = &values[i..i + 3];
                     position_vec.push(Vec3::from_slice(slice));
                 }
             }
             "NORMAL" => {
-                let values = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
+                let values = read_accessor_f32(&accessor, mesh_data)?;
                 for i in (0..accessor.count() * 3).step_by(3) {
                     let slice = &values[i..i + 3];
                     normal_vec.push(Vec3::from_slice(slice));
                 }
             }
             "TANGENT" => {
-                let values = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
+                let values = read_accessor_f32(&accessor, mesh_data)?;
                 for i in (0..accessor.count() * 4).step_by(4) {
                     let slice = &values[i..i + 4];
                     tangent_vec.push(Vec4::from_slice(slice));
                 }
             }
             "TEXCOORD_0" => {
-                let values = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
+                let values = read_accessor_f32(&accessor, mesh_data)?;
                 for i in (0..accessor.count() * 2).step_by(2) {
                     let slice = &values[i..i + 2];
                     texcoord0_vec.push(Vec2::from_slice(slice));
                 }
             }
             "TEXCOORD_1" => {
-                let values = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
+                let values = read_accessor_f32(&accessor, mesh_data)?;
                 for i in (0..accessor.count() * 2).step_by(2) {
                     let slice = &values[i..i + 2];
                     texcoord1_vec.push(Vec2::from_slice(slice));
                 }
             }
+            "JOINTS_0" => {
+                let values = read_accessor_u32(&accessor, mesh_data)?;
+                for slice in values.chunks_exact(4) {
+                    joints_vec.push(UVec4::from_slice(slice));
+                }
+            }
+            "WEIGHTS_0" => {
+                let values = read_accessor_f32(&accessor, mesh_data)?;
+                for slice in values.chunks_exact(4) {
+                    weights_vec.push(Vec4::from_slice(slice));
+                }
+            }
             "COLOR_0" => {
-                let values = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
-                for i in (0..accessor.count() * 4).step_by(4) {
-                    let slice = &values[i..i + 4];
-                    color_vec.push(Vec4::from_slice(slice));
+                // Colors can be RGB or RGBA
+                let values = read_accessor_f32(&accessor, mesh_data)?;
+                let components = accessor.dimensions().multiplicity();
+                for slice in values.chunks_exact(components) {
+                    color_vec.push(match slice {
+                        [r, g, b] => Vec4::new(*r, *g, *b, 1.0),
+                        [r, g, b, a] => Vec4::new(*r, *g, *b, *a),
+                        _ => return Err(AssetError::MalformedData(format!("COLOR_0 can not have {components} components"))),
+                    });
                 }
             }
             _ => {}
         }
     }
 
-    // Find indices
-    {
-        // Get accessor
-        let accessor = primitive.indices().unwrap();
-
-        // Get buffer view
-        let bufferview = accessor.view().unwrap();
-
-        // Find location in buffer
-        let buffer_index = bufferview.buffer().index();
-        let buffer_offset = bufferview.offset();
-        let buffer_end = bufferview.offset() + bufferview.length();
+    // Make sure every attribute has a value for every vertex
+    let vertex_count = position_vec.len();
+    for attribute_len in [normal_vec.len(), tangent_vec.len(), texcoord0_vec.len(), texcoord1_vec.len(), color_vec.len(), joints_vec.len(), weights_vec.len()] {
+        if attribute_len != 0 && attribute_len != vertex_count {
+            return Err(AssetError::MalformedData(format!(
+                "primitive has {vertex_count} positions, but an attribute with {attribute_len} values"
+            )));
+        }
+    }
 
-        // Find location in buffer
-        let buffer_base = &mesh_data[buffer_index].0;
-        let buffer_slice = buffer_base.get(buffer_offset..buffer_end).unwrap();
+    // Find indices, non-indexed primitives just use every vertex in order
+    let indices = match primitive.indices() {
+        Some(accessor) => read_accessor_u32(&accessor, mesh_data)?,
+        None => (0..vertex_count as u32).collect(),
+    };
 
-        // Convert from raw buffer to f32 vec - this is incredibly cursed but it'll have to do
-        let indices_f32 = convert_gltf_buffer_to_f32(buffer_slice, &accessor);
-        for index in indices_f32 {
-            indices.push(index as u16);
-        }
+    // Make sure every index points to an existing vertex
+    if let Some(index) = indices.iter().find(|index| **index as usize >= vertex_count) {
+        return Err(AssetError::MalformedData(format!(
+            "index {index} is out of range for a primitive with {vertex_count} vertices"
+        )));
     }
 
+    // Strips, loops and fans are turned into lists, so the renderers only have to deal with 3 primitive types
+    let (primitive_type, indices) = convert_to_list(primitive.mode(), indices);
+
     // Create vertex array
     let mut mesh_out = Mesh {
-        verts: Vec::new(),
+        verts: Vec::with_capacity(vertex_count),
+        indices: Indices::new(indices, vertex_count),
+        primitive_type,
+        morph_targets: load_morph_targets(primitive, mesh_data, vertex_count)?,
         buffer: None,
     };
-    for index in indices {
+    for index in 0..vertex_count {
         let mut vertex = Vertex {
             position: Vec3::new(0., 0., 0.),
             normal: Vec3::new(0., 0., 0.),
@@ -179,155 +417,141 @@ fn create_vertex_array(
             color: Vec4::new(1., 1., 1., 1.),
             uv0: Vec2::new(0., 0.),
             uv1: Vec2::new(0., 0.),
+            joints: UVec4::new(0, 0, 0, 0),
+            weights: Vec4::new(0., 0., 0., 0.),
         };
         if !position_vec.is_empty() {
-            let pos3 = position_vec[index as usize];
-            vertex.position = (local_matrix * pos3.extend(1.0)).xyz();
+            vertex.position = position_vec[index];
         }
         if !normal_vec.is_empty() {
-            vertex.normal = local_matrix.transform_vector3(normal_vec[index as usize]);
+            vertex.normal = normal_vec[index];
         }
         if !tangent_vec.is_empty() {
-            let tangent_vec3 = local_matrix.transform_vector3(tangent_vec[index as usize].xyz());
-            vertex.tangent.x = tangent_vec3.x;
-            vertex.tangent.y = tangent_vec3.y;
-            vertex.tangent.z = tangent_vec3.z;
-            vertex.tangent.w = tangent_vec[index as usize].w;
+            vertex.tangent = tangent_vec[index];
         }
         if !texcoord0_vec.is_empty() {
-            vertex.uv0 = texcoord0_vec[index as usize];
+            vertex.uv0 = texcoord0_vec[index];
         }
         if !texcoord1_vec.is_empty() {
-            vertex.uv1 = texcoord1_vec[index as usize];
+            vertex.uv1 = texcoord1_vec[index];
+        }
+        if !joints_vec.is_empty() && !weights_vec.is_empty() {
+            vertex.joints = joints_vec[index];
+            vertex.weights = weights_vec[index];
         }
         if !color_vec.is_empty() {
-            vertex.color.x = f32::powf(color_vec[index as usize].x, 1.0 / 2.2);
-            if vertex.color.x > 1.0 {
-                vertex.color.x = 1.0
-            }
-            vertex.color.y = f32::powf(color_vec[index as usize].y, 1.0 / 2.2);
-            if vertex.color.y > 1.0 {
-                vertex.color.y = 1.0
-            }
-            vertex.color.z = f32::powf(color_vec[index as usize].z, 1.0 / 2.2);
-            if vertex.color.z > 1.0 {
-                vertex.color.z = 1.0
-            }
+            // Kept in linear RGB, the shaders convert the result to sRGB
+            vertex.color = color_vec[index];
         }
         mesh_out.verts.push(vertex);
     }
-    mesh_out
-}
 
-fn traverse_nodes(
-    node: &gltf::Node,
-    mesh_data: &Vec<Data>,
-    local_transform: Mat4,
-    primitives_processed: &mut HashMap<String, Mesh>,
-) {
-    // Convert translation in GLTF model to a Mat4.
-    let node_transform = Transform {
-        scale: glam::vec3(
-            node.transform().decomposed().2[0],
-            node.transform().decomposed().2[1],
-            node.transform().decomposed().2[2],
-        ),
-        rotation: glam::quat(
-            node.transform().decomposed().1[0],
-            node.transform().decomposed().1[1],
-            node.transform().decomposed().1[2],
-            node.transform().decomposed().1[3],
-        ),
-        translation: glam::vec3(
-            node.transform().decomposed().0[0],
-            node.transform().decomposed().0[1],
-            node.transform().decomposed().0[2],
-        ),
-    };
-
-    let new_local_transform = local_transform * node_transform.local_matrix();
-
-    // If it has a mesh, process it
-    let mesh = node.mesh();
-    if let Some(mesh) = mesh {
-        // Get mesh
-        let primitives = mesh.primitives();
-
-        for primitive in primitives {
-            let mut mesh_buffer_data =
-                create_vertex_array(&primitive, mesh_data, new_local_transform);
-            let material = String::from(primitive.material().name().unwrap_or("None"));
-            #[allow(clippy::map_entry)] // This was really annoying and made the code less readable
-            if primitives_processed.contains_key(&material) {
-                let mesh: &mut Mesh = primitives_processed.get_mut(&material).unwrap();
-                mesh.verts.append(&mut mesh_buffer_data.verts);
-            } else {
-                primitives_processed.insert(material, mesh_buffer_data);
-            }
-        }
+    // Lighting needs normals, and the tangents are generated from them
+    if normal_vec.is_empty() {
+        mesh_out.generate_normals(options.normals);
     }
 
-    // If it has children, process those
-    for child in node.children() {
-        traverse_nodes(&child, mesh_data, new_local_transform, primitives_processed);
+    // Normal mapping needs tangents, most files don't have them
+    if tangent_vec.is_empty() || options.regenerate_tangents {
+        mesh_out.generate_tangents();
     }
+    Ok(mesh_out)
 }
 
 impl Model {
-    pub(crate) fn load_gltf(path: &Path, renderer: &mut Renderer) -> Result<Model, String> {
-        let mut model = Model::new();
+    pub fn load_gltf<B: RenderBackend + ?Sized>(path: &Path, renderer: &mut B) -> Result<Model, AssetError> {
+        Self::load_gltf_with_options(path, renderer, &LoadOptions::default())
+    }
 
-        // Load GLTF from file
-        let gltf_file = gltf::import(path);
-        if gltf_file.is_err() {
-            return Err("Failed to load GLTF file {path}!".to_string());
-        }
-        let (gltf_document, mesh_data, image_data) = gltf_file.unwrap();
-
-        // Loop over each scene
-        let scene = gltf_document.default_scene();
-        if let Some(scene) = scene {
-            // For each scene, get the nodes
-            for node in scene.nodes() {
-                traverse_nodes(&node, &mesh_data, Mat4::IDENTITY, &mut model.meshes);
-            }
-        }
+    // External buffers and images are read from the folder the file is in
+    pub fn load_gltf_with_options<B: RenderBackend + ?Sized>(path: &Path, renderer: &mut B, options: &LoadOptions) -> Result<Model, AssetError> {
+        let bytes = std::fs::read(path).map_err(|source| AssetError::Io { path: path.to_path_buf(), source })?;
+        let folder = path.parent().unwrap_or(Path::new("./"));
+        Self::load_gltf_from_named_slice(&bytes, path, &mut |uri| std::fs::read(folder.join(uri)), renderer, options)
+    }
 
-        // Get all the textures from the GLTF
-        for material in gltf_document.materials() {
-            let mut new_material = Material::new(); // this is unused for now
-
-            // Get PBR parameters
-            new_material.scl_rgh = material.pbr_metallic_roughness().roughness_factor();
-            new_material.scl_mtl = material.pbr_metallic_roughness().metallic_factor();
-            new_material.scl_emm = material.emissive_factor().into();
-
-            // Try to find textures
-            let tex_info_alb = material.pbr_metallic_roughness().base_color_texture();
-            let _tex_info_mtl_rgh = material
-                .pbr_metallic_roughness()
-                .metallic_roughness_texture();
-            let _tex_info_nrm = material.normal_texture();
-            let _tex_info_emm = material.emissive_texture();
-
-            // Get the texture data
-            if let Some(tex) = tex_info_alb {
-                // todo: add texture support
-                new_material.tex_alb = renderer.upload_texture(&mut Texture::load_texture_from_gltf_image(&image_data[tex.texture().source().index()])) as i32;            
-            }
+    // Loads a glTF JSON or GLB file that is already in memory, like one embedded with include_bytes!.
+    // Data URIs and the GLB blob are read from the bytes, every other buffer and image is read with the resolver.
+    pub fn load_gltf_from_slice<B: RenderBackend + ?Sized>(
+        bytes: &[u8],
+        resolver: &mut Resolver,
+        renderer: &mut B,
+        options: &LoadOptions,
+    ) -> Result<Model, AssetError> {
+        Self::load_gltf_from_named_slice(bytes, Path::new(MEMORY_PATH), resolver, renderer, options)
+    }
+
+    // Same as Model::load_gltf_from_slice, but reads the file from a stream first, like a file in an archive
+    pub fn load_gltf_from_reader<B: RenderBackend + ?Sized>(
+        reader: &mut dyn Read,
+        resolver: &mut Resolver,
+        renderer: &mut B,
+        options: &LoadOptions,
+    ) -> Result<Model, AssetError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|source| AssetError::Io { path: PathBuf::from(MEMORY_PATH), source })?;
+        Self::load_gltf_from_slice(&bytes, resolver, renderer, options)
+    }
 
-            model.materials.insert(
-                String::from(material.name().unwrap_or("untitled")),
-                new_material,
-            );
+    fn load_gltf_from_named_slice<B: RenderBackend + ?Sized>(
+        bytes: &[u8],
+        path: &Path,
+        resolver: &mut Resolver,
+        renderer: &mut B,
+        options: &LoadOptions,
+    ) -> Result<Model, AssetError> {
+        let mut model = Model::new();
+        let (gltf_document, mesh_data, image_data) = import_slice(bytes, path, resolver)?;
+
+        // Load every mesh, keeping each primitive separate so it can be drawn with its own node's transform
+        for mesh in gltf_document.meshes() {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives() {
+                primitives.push(Primitive {
+                    mesh: create_vertex_array(&primitive, &mesh_data, options)?,
+                    material: primitive.material().index(),
+                });
+            }
+            model.meshes.push(primitives);
         }
+
+        // Then build the node tree, and pick the default scene. Files don't have to set one, then we use the first scene.
+        model.nodes = load_nodes(&gltf_document)?;
+        model.scenes = load_scenes(&gltf_document);
+        model.set_scene(gltf_document.default_scene().map_or(0, |scene| scene.index()));
+        model.skins = load_skins(&gltf_document, &mesh_data)?;
+        check_joint_indices(&model)?;
+        model.animations = load_animations(&gltf_document, &mesh_data)?;
+        model.cameras = load_cameras(&gltf_document);
+        model.lights = load_lights(&gltf_document);
+        model.update_world_matrices();
+
+        // Materials are referenced by index, names are only there to find them by
+        model.materials = load_materials(&gltf_document, &image_data, renderer)?;
         Ok(model)
     }
 
-    pub(crate) fn new() -> Model {
+    pub fn new() -> Model {
         Model {
-            meshes: HashMap::new(),
-            materials: HashMap::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            default_material: Material::new(),
+            nodes: Vec::new(),
+            root_nodes: Vec::new(),
+            scenes: Vec::new(),
+            scene: None,
+            skins: Vec::new(),
+            animations: Vec::new(),
+            cameras: Vec::new(),
+            lights: Vec::new(),
         }
     }
 }
+
+impl Default for Model {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,71 @@
+use glam::Vec3;
+
+use crate::accessor::read_accessor_f32;
+use crate::error::AssetError;
+use crate::structs::Vertex;
+
+// Offsets that are added to the vertices of a mesh, scaled by the node's weight for this target.
+// Each list is either empty, when the target doesn't change that attribute, or has one delta per vertex.
+#[derive(Debug, Clone, Default)]
+pub struct MorphTarget {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub tangents: Vec<Vec3>,
+}
+
+// CPU implementation of morph target blending. This happens before skinning, like the glTF spec says.
+// Weights beyond the number of targets are ignored, and missing weights count as 0.
+pub fn blend_vertices(verts: &[Vertex], targets: &[MorphTarget], weights: &[f32]) -> Vec<Vertex> {
+    let mut blended = verts.to_vec();
+    for (target, weight) in targets.iter().zip(weights) {
+        if *weight == 0.0 {
+            continue;
+        }
+        for (vertex, delta) in blended.iter_mut().zip(&target.positions) {
+            vertex.position += *delta * *weight;
+        }
+        for (vertex, delta) in blended.iter_mut().zip(&target.normals) {
+            vertex.normal += *delta * *weight;
+        }
+        for (vertex, delta) in blended.iter_mut().zip(&target.tangents) {
+            vertex.tangent += (*delta * *weight).extend(0.0);
+        }
+    }
+    return blended;
+}
+
+// Whether blending these weights would change anything, so unmorphed meshes can skip the work
+pub fn has_active_weights(targets: &[MorphTarget], weights: &[f32]) -> bool {
+    !targets.is_empty() && weights.iter().take(targets.len()).any(|weight| *weight != 0.0)
+}
+
+pub(crate) fn load_morph_targets(
+    primitive: &gltf::Primitive,
+    mesh_data: &[gltf::buffer::Data],
+    vertex_count: usize,
+) -> Result<Vec<MorphTarget>, AssetError> {
+    let read_deltas = |accessor: Option<gltf::Accessor>| -> Result<Vec<Vec3>, AssetError> {
+        let Some(accessor) = accessor else {
+            return Ok(Vec::new());
+        };
+        let deltas: Vec<Vec3> = read_accessor_f32(&accessor, mesh_data)?.chunks_exact(3).map(Vec3::from_slice).collect();
+        if deltas.len() != vertex_count {
+            return Err(AssetError::MalformedData(format!(
+                "morph target accessor {} has {} deltas, but the primitive has {vertex_count} vertices",
+                accessor.index(),
+                deltas.len()
+            )));
+        }
+        Ok(deltas)
+    };
+
+    let mut targets = Vec::new();
+    for target in primitive.morph_targets() {
+        targets.push(MorphTarget {
+            positions: read_deltas(target.positions())?,
+            normals: read_deltas(target.normals())?,
+            tangents: read_deltas(target.tangents())?,
+        });
+    }
+    return Ok(targets);
+}
@@ -0,0 +1,101 @@
+use std::f32::consts::PI;
+
+use glam::{Vec3, Vec4, Vec4Swizzles};
+
+use crate::light::PlacedLight;
+
+// Perfectly smooth surfaces would have an infinitely small highlight, which punctual lights can't hit
+pub const MIN_ROUGHNESS: f32 = 0.03;
+
+// Everything the BRDF needs to know about a point on a surface, after the material's textures and factors are combined.
+// This is the CPU reference of the Metal fragment shader, which does the exact same math.
+#[derive(Debug, Clone, Copy)]
+pub struct Surface {
+    pub base_color: Vec3, // Linear RGB
+    pub metallic: f32,
+    pub roughness: f32, // Perceptual roughness, it's squared before it's used
+    pub normal: Vec3,   // Normalized, with the normal map already applied
+    pub occlusion: f32, // 1.0 is not occluded at all
+    pub emissive: Vec3, // Linear RGB
+}
+
+// The glTF metallic-roughness BRDF, from appendix B of the spec: Lambertian diffuse plus Cook-Torrance specular
+// with the GGX distribution, the height-correlated Smith visibility term and Schlick's Fresnel approximation.
+// Returns how much of the light coming from to_light is reflected towards to_view, both pointing away from the surface.
+pub fn brdf(surface: &Surface, to_view: Vec3, to_light: Vec3) -> Vec3 {
+    let n_dot_l = surface.normal.dot(to_light);
+    if n_dot_l <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let n_dot_v = surface.normal.dot(to_view).clamp(0.0, 1.0);
+    let half = (to_view + to_light).normalize_or_zero();
+    let n_dot_h = surface.normal.dot(half).clamp(0.0, 1.0);
+    let v_dot_h = to_view.dot(half).clamp(0.0, 1.0);
+
+    // Metals don't have a diffuse color, and reflect their base color instead of 4% white
+    let diffuse_color = surface.base_color.lerp(Vec3::ZERO, surface.metallic);
+    let f0 = Vec3::splat(0.04).lerp(surface.base_color, surface.metallic);
+    let fresnel = f0 + (Vec3::ONE - f0) * (1.0 - v_dot_h).powi(5);
+
+    let alpha = surface.roughness.clamp(MIN_ROUGHNESS, 1.0).powi(2);
+    let specular = fresnel * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+    let diffuse = (Vec3::ONE - fresnel) * diffuse_color / PI;
+    return diffuse + specular;
+}
+
+// How many microfacets point along the half vector
+pub fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha_squared = alpha * alpha;
+    let f = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * f * f);
+}
+
+// Shadowing and masking of the microfacets, divided by the 4 * n_dot_l * n_dot_v of the Cook-Torrance denominator
+pub fn visibility_smith_ggx(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32 {
+    let alpha_squared = alpha * alpha;
+    let ggx_v = n_dot_l * (n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared).sqrt();
+    let ggx_l = n_dot_v * (n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared).sqrt();
+    let sum = ggx_v + ggx_l;
+    if sum <= 0.0 {
+        return 0.0;
+    }
+    return 0.5 / sum;
+}
+
+// Shades a surface with every light, in linear RGB. Like the Khronos reference viewer does without image based lighting,
+// occlusion darkens the reflected light. Emission is added on top, occlusion doesn't affect it.
+pub fn shade_surface(lights: &[PlacedLight], position: Vec3, to_view: Vec3, surface: &Surface) -> Vec3 {
+    let mut result = Vec3::ZERO;
+    for light in lights {
+        let (radiance, to_light) = light.incoming_light(position);
+        result += brdf(surface, to_view, to_light) * radiance * surface.normal.dot(to_light).max(0.0);
+    }
+    return result * surface.occlusion + surface.emissive;
+}
+
+// Bends the interpolated vertex normal with a tangent space normal map sample, in 0..1 like it comes out of the texture.
+// The bitangent is cross(normal, tangent) * tangent.w, the same convention Mesh::generate_tangents uses.
+pub fn perturb_normal(normal: Vec3, tangent: Vec4, sample: Vec3, scale: f32) -> Vec3 {
+    let normal = normal.normalize_or_zero();
+    // Re-orthogonalize, interpolation across a triangle skews the tangent
+    let tangent_xyz = (tangent.xyz() - normal * normal.dot(tangent.xyz())).normalize_or_zero();
+    if normal == Vec3::ZERO || tangent_xyz == Vec3::ZERO {
+        return normal;
+    }
+    let handedness = if tangent.w < 0.0 { -1.0 } else { 1.0 };
+    let bitangent = normal.cross(tangent_xyz) * handedness;
+    let bent = (sample * 2.0 - Vec3::ONE) * Vec3::new(scale, scale, 1.0);
+    return (tangent_xyz * bent.x + bitangent * bent.y + normal * bent.z).try_normalize().unwrap_or(normal);
+}
+
+// Base color and emissive textures are stored in sRGB, shading happens in linear RGB
+pub fn srgb_to_linear(color: Vec3) -> Vec3 {
+    let channel = |c: f32| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+    Vec3::new(channel(color.x), channel(color.y), channel(color.z))
+}
+
+// The framebuffer is stored in sRGB, like the textures
+pub fn linear_to_srgb(color: Vec3) -> Vec3 {
+    let channel = |c: f32| if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    Vec3::new(channel(color.x), channel(color.y), channel(color.z))
+}
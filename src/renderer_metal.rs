@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::mem;
+use std::path::PathBuf;
+
+use cocoa::appkit::NSView;
+use cocoa::base::YES;
+use core_graphics_types::geometry::CGSize;
+use glam::{Mat4, Vec4};
+use metal::{Device, MetalLayer, MTLPixelFormat, RenderPipelineState, RenderPipelineDescriptor, CommandQueue, Library, MTLResourceOptions, RenderPassDescriptor, MTLClearColor, MTLStoreAction, MTLScissorRect, MTLPrimitiveType, MTLViewport, Buffer, TextureDescriptor, MTLRegion, MTLSize, MTLOrigin, DepthStencilDescriptor, MTLCompareFunction, DepthStencilState, MTLTextureUsage, MTLStorageMode, MTLIndexType, SamplerDescriptor, SamplerState, MTLSamplerMinMagFilter, MTLSamplerMipFilter, MTLSamplerAddressMode, CompileOptions, MTLBlendOperation, MTLBlendFactor, MTLWinding, MTLCullMode};
+use metal::foreign_types::ForeignType;
+use winit::platform::macos::WindowExtMacOS;
+use metal::MTLLoadAction;
+use winit::window::Window;
+
+use crate::camera::Projection;
+use crate::error::AssetError;
+use crate::graphics::{ModelQueueEntry, RenderBackend};
+use crate::helpers::normal_matrix;
+use crate::handle::{HandleError, ModelHandle, ResourcePool, TextureHandle};
+use crate::light::{LightData, PlacedLight};
+use crate::material::MaterialData;
+use crate::mesh::{Indices, Mesh, Model, PrimitiveType};
+use crate::morph::{blend_vertices, has_active_weights};
+use crate::structs::{Vertex, ConstBuffer, Transform};
+use crate::texture::{FilterMode, Sampler, Texture, WrapMode};
+
+// The shaders every Metal renderer uses, built into the binary and compiled when the renderer is created
+pub const SHADER_SOURCE: &str = include_str!("../metal/shaders/hello_triangle.metal");
+// Where SHADER_SOURCE comes from, to point compile errors at
+const SHADER_PATH: &str = "metal/shaders/hello_triangle.metal";
+
+struct MeshBuffers {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u64,
+    index_type: MTLIndexType,
+    primitive_type: MTLPrimitiveType,
+}
+
+pub struct MetalRenderer{
+    pub device: Option<Device>,
+    pipeline_state: Option<RenderPipelineState>,
+    library: Option<Library>,
+    command_queue: Option<CommandQueue>,
+    layer: Option<MetalLayer>,
+    offscreen_texture: Option<metal::Texture>, // Only used when rendering headless, in which case there's no layer
+    framebuffer_size: (u32, u32),
+    const_buffer_gpu: Vec<Buffer>,
+    const_buffer_cpu: ConstBuffer,
+    projection: Projection,
+    lights: Vec<PlacedLight>, // Set with set_lights, the lights in the drawn models are added to these every frame
+    light_buffer_gpu: Option<Buffer>,
+    joint_buffer_gpu: Vec<Buffer>, // Joint matrices of every draw call in this frame
+    morph_buffer_gpu: Vec<Buffer>, // Blended vertices of every morphed mesh in this frame
+    loaded_models: ResourcePool<Model>,
+    loaded_textures: ResourcePool<metal::Texture, Texture>,
+    mesh_buffers: ResourcePool<MeshBuffers, Mesh>,
+    model_queue: Vec<ModelQueueEntry>,
+    depth_texture: Option<metal::Texture>,
+    depth_stencil_state: Option<DepthStencilState>,
+    tex_white: Option<TextureHandle>,
+    sampler_states: HashMap<Sampler, SamplerState>, // Created the first time a sampler is used, materials share them
+}
+
+impl MetalRenderer{
+    pub fn new(window: &Window) -> Result<Self, AssetError> {
+        let mut renderer = Self::new_without_target()?;
+
+        // Create metal layer
+        renderer.layer = Some(MetalLayer::new());
+        renderer.layer.as_ref().unwrap().set_device(renderer.device.as_ref().unwrap());
+        renderer.layer.as_ref().unwrap().set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+        renderer.layer.as_ref().unwrap().set_presents_with_transaction(false);
+
+        // Create view - a sort of canvas where you draw graphics using Metal commands
+        unsafe {
+            let view = window.ns_view() as cocoa::base::id;
+            view.setWantsLayer(YES);
+            view.setLayer(renderer.layer.as_ref().unwrap().as_ptr() as cocoa::base::id);
+        }
+
+        // Store the view size
+        let drawable_size = window.inner_size();
+
+        // Resize framebuffer
+        renderer.resize_framebuffer(drawable_size.width, drawable_size.height);
+
+        return Ok(renderer);
+    }
+
+    // Creates a renderer that draws into an offscreen texture instead of a window, which can be read back with read_framebuffer
+    pub fn new_headless(width: u32, height: u32) -> Result<Self, AssetError> {
+        let mut renderer = Self::new_without_target()?;
+        renderer.resize_framebuffer(width, height);
+        return Ok(renderer);
+    }
+
+    fn new_without_target() -> Result<Self, AssetError> {
+        // Initialize renderer with none
+        let mut renderer = MetalRenderer {
+            device: None,
+            pipeline_state: None,
+            command_queue: None,
+            library: None,
+            layer: None,
+            offscreen_texture: None,
+            framebuffer_size: (0, 0),
+            const_buffer_cpu: ConstBuffer{
+                model_matrix: Mat4::IDENTITY,
+                view_matrix: Mat4::IDENTITY,
+                proj_matrix: Mat4::IDENTITY,
+                normal_matrix: Mat4::IDENTITY,
+                joint_count: 0,
+                light_count: 0,
+                _padding: [0; 2],
+                camera_position: Vec4::W,
+            },
+            projection: Projection::default(),
+            lights: Vec::new(),
+            light_buffer_gpu: None,
+            const_buffer_gpu: Vec::new(),
+            joint_buffer_gpu: Vec::new(),
+            morph_buffer_gpu: Vec::new(),
+            model_queue: Vec::new(),
+            loaded_models: ResourcePool::new(),
+            loaded_textures: ResourcePool::new(),
+            mesh_buffers: ResourcePool::new(),
+            depth_texture: None,
+            depth_stencil_state: None,
+            tex_white: None,
+            sampler_states: HashMap::new(),
+        };
+
+        // Create device
+        renderer.device = Some(Device::system_default().expect("Could not create device."));
+
+        // Create command queue
+        renderer.command_queue = Some(renderer.device.as_ref().unwrap().new_command_queue());
+
+        // Compile the shaders
+        renderer.compile_library(SHADER_SOURCE)?;
+
+        // Initialize default white texture
+        let mut tex_white = Texture {
+            gl_id: 0,
+            width: 1,
+            height: 1,
+            depth: 1,
+            data: vec![0xFFFFFFFFu32]
+        };
+        renderer.tex_white = Some(renderer.upload_texture(&mut tex_white));
+
+        return Ok(renderer);
+    }
+
+    // Compiles the shaders from source, so the library can never be out of date with the Rust side of the buffer layouts
+    fn compile_library(&mut self, source: &str) -> Result<(), AssetError> {
+        let library = self.device.as_ref().unwrap().new_library_with_source(source, &CompileOptions::new());
+        let library = library.map_err(|message| AssetError::Parse { path: PathBuf::from(SHADER_PATH), message })?;
+        self.library = Some(library);
+        return Ok(());
+    }
+
+    // Returns the texture to bind for a material slot, falling back to white when there is none or the handle is invalid
+    fn resolve_texture(&self, texture: Option<TextureHandle>) -> &metal::TextureRef {
+        let white = self.loaded_textures.get(self.tex_white.unwrap()).unwrap();
+        match texture {
+            Some(texture) => match self.loaded_textures.get(texture) {
+                Ok(texture_gpu) => texture_gpu,
+                Err(err) => {
+                    println!("Can not bind texture {texture:?}: {err}");
+                    white
+                }
+            },
+            None => white,
+        }
+    }
+
+    // Returns the sampler state for a sampler, creating it if no material used it before
+    fn sampler_state<'a>(sampler_states: &'a mut HashMap<Sampler, SamplerState>, device: &Device, sampler: Sampler) -> &'a metal::SamplerStateRef {
+        sampler_states.entry(sampler).or_insert_with(|| {
+            let filter = |mode: FilterMode| match mode {
+                FilterMode::Point => MTLSamplerMinMagFilter::Nearest,
+                FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
+            };
+            let address_mode = |mode: WrapMode| match mode {
+                WrapMode::Repeat => MTLSamplerAddressMode::Repeat,
+                WrapMode::Mirror => MTLSamplerAddressMode::MirrorRepeat,
+                WrapMode::Clamp => MTLSamplerAddressMode::ClampToEdge,
+            };
+            let sampler_desc = SamplerDescriptor::new();
+            sampler_desc.set_mag_filter(filter(sampler.filter_mode_mag));
+            sampler_desc.set_min_filter(filter(sampler.filter_mode_min));
+            sampler_desc.set_mip_filter(match (sampler.mipmap_enabled, sampler.filter_mode_mipmap) {
+                (false, _) => MTLSamplerMipFilter::NotMipmapped,
+                (true, FilterMode::Point) => MTLSamplerMipFilter::Nearest,
+                (true, FilterMode::Linear) => MTLSamplerMipFilter::Linear,
+            });
+            sampler_desc.set_address_mode_s(address_mode(sampler.wrap_mode_s));
+            sampler_desc.set_address_mode_t(address_mode(sampler.wrap_mode_t));
+            device.new_sampler(&sampler_desc)
+        })
+    }
+
+    fn update_const_buffer_gpu(buffer_gpu: &mut Buffer, buffer_cpu: &ConstBuffer){
+        let buffer_gpu_data = buffer_gpu.contents();
+        unsafe {
+            std::ptr::copy(buffer_cpu, buffer_gpu_data as *mut ConstBuffer, 1);
+        }
+    }
+}
+
+impl RenderBackend for MetalRenderer {
+    fn prepare_pipeline_state (
+        &mut self,
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+    ) {
+        // Get compiled functions from the library
+        let vertex_function = self.library.as_ref().unwrap().get_function(vertex_shader_path, None).unwrap();
+        let fragment_function = self.library.as_ref().unwrap().get_function(fragment_shader_path, None).unwrap();
+
+        // Create pipeline state descriptor - handles things like shader program, buffer to render to, blend mode, etc.
+        let pipeline_state_desc = RenderPipelineDescriptor::new();
+        pipeline_state_desc.set_vertex_function(Some(&vertex_function));
+        pipeline_state_desc.set_fragment_function(Some(&fragment_function));
+        pipeline_state_desc.set_depth_attachment_pixel_format(MTLPixelFormat::Depth32Float);
+
+        let color_attachment = pipeline_state_desc.color_attachments().object_at(0).unwrap();
+        color_attachment.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+        // Source-over blending. Only blended materials output an alpha below 1, so this leaves the others opaque.
+        color_attachment.set_blending_enabled(true);
+        color_attachment.set_rgb_blend_operation(MTLBlendOperation::Add);
+        color_attachment.set_alpha_blend_operation(MTLBlendOperation::Add);
+        color_attachment.set_source_rgb_blend_factor(MTLBlendFactor::SourceAlpha);
+        color_attachment.set_destination_rgb_blend_factor(MTLBlendFactor::OneMinusSourceAlpha);
+        color_attachment.set_source_alpha_blend_factor(MTLBlendFactor::One);
+        color_attachment.set_destination_alpha_blend_factor(MTLBlendFactor::OneMinusSourceAlpha);
+
+        self.pipeline_state = Some(self.device.as_ref().unwrap().new_render_pipeline_state(&pipeline_state_desc).unwrap());
+
+        let depth_stencil_desc = DepthStencilDescriptor::new();
+        depth_stencil_desc.set_depth_write_enabled(true);
+        depth_stencil_desc.set_depth_compare_function(MTLCompareFunction::Less);
+        self.depth_stencil_state = Some(self.device.as_ref().unwrap().new_depth_stencil_state(&depth_stencil_desc));
+    }
+
+    fn upload_vertex_buffer(&mut self, mesh: &mut Mesh) {
+        if mesh.is_empty() {
+            return;
+        }
+
+        // Create the vertex and index buffers on the device
+        let vertex_buffer = self.device.as_ref().unwrap().new_buffer_with_data(
+            mesh.verts.as_ptr() as *const _,
+            (mesh.verts.len() * mem::size_of::<Vertex>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+        );
+        let index_buffer = self.device.as_ref().unwrap().new_buffer_with_data(
+            mesh.indices.as_ptr() as *const _,
+            (mesh.indices.len() * mesh.indices.stride()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+        );
+        mesh.buffer = Some(self.mesh_buffers.insert(MeshBuffers {
+            vertex_buffer,
+            index_buffer,
+            index_count: mesh.indices.len() as u64,
+            index_type: match mesh.indices {
+                Indices::U16(_) => MTLIndexType::UInt16,
+                Indices::U32(_) => MTLIndexType::UInt32,
+            },
+            primitive_type: match mesh.primitive_type {
+                PrimitiveType::Points => MTLPrimitiveType::Point,
+                PrimitiveType::Lines => MTLPrimitiveType::Line,
+                PrimitiveType::Triangles => MTLPrimitiveType::Triangle,
+            },
+        }));
+    }
+
+    fn begin_frame(&mut self) {
+        self.model_queue.clear();
+        self.const_buffer_gpu.clear();
+        self.joint_buffer_gpu.clear();
+        self.morph_buffer_gpu.clear();
+    }
+
+    fn end_frame(&mut self) {
+        // Get the texture to render to - either the next framebuffer of the window, or our offscreen texture when headless
+        let drawable = match self.layer.as_ref() {
+            Some(layer) => match layer.next_drawable() {
+                Some(drawable) => Some(drawable),
+                None => return,
+            },
+            None => None,
+        };
+        let target_texture = match drawable {
+            Some(drawable) => drawable.texture(),
+            None => self.offscreen_texture.as_ref().unwrap().as_ref(),
+        };
+        let size = CGSize::new(target_texture.width() as f64, target_texture.height() as f64);
+
+        // Set up framebuffer
+        let render_pass_descriptor = RenderPassDescriptor::new();
+        let color_attachment = render_pass_descriptor.color_attachments().object_at(0).expect("Failed to get color attachment");
+        color_attachment.set_texture(Some(target_texture));
+        color_attachment.set_load_action(MTLLoadAction::Clear);
+        color_attachment.set_clear_color(MTLClearColor::new(0.1, 0.1, 0.2, 1.0));
+        color_attachment.set_store_action(MTLStoreAction::Store);
+
+        // Set up depth buffer
+        let depth_attachment = render_pass_descriptor.depth_attachment().unwrap();
+        depth_attachment.set_texture(Some(self.depth_texture.as_ref().unwrap()));
+        depth_attachment.set_load_action(MTLLoadAction::Clear);
+        depth_attachment.set_clear_depth(1.0);
+        depth_attachment.set_store_action(MTLStoreAction::Store);
+
+        // Set up command buffer
+        let command_buffer = self.command_queue.as_ref().unwrap().new_command_buffer();
+        let command_encoder = command_buffer.new_render_command_encoder(render_pass_descriptor);
+
+        // Record mesh draw calls
+        command_encoder.set_render_pipeline_state(self.pipeline_state.as_ref().unwrap().as_ref());
+        command_encoder.set_depth_stencil_state(self.depth_stencil_state.as_ref().unwrap());
+        command_encoder.set_front_facing_winding(MTLWinding::CounterClockwise);
+        command_encoder.set_scissor_rect(MTLScissorRect{x: 0, y: 0, width: size.width as u64, height: size.height as u64});
+        command_encoder.set_viewport(MTLViewport{
+            originX: 0.0,
+            originY: 0.0,
+            width: size.width,
+            height: size.height,
+            znear: -1.0,
+            zfar: 1.0,
+        });
+
+        // Gather the lights of every model first, so they also light the models drawn before them
+        let mut lights: Vec<LightData> = self.lights.iter().map(|light| light.to_light_data()).collect();
+        for entry in &self.model_queue {
+            if let Ok(model) = self.loaded_models.get_mut(entry.model) {
+                model.update_world_matrices();
+                lights.extend(model.placed_lights(entry.transform.local_matrix()).iter().map(|light| light.to_light_data()));
+            }
+        }
+        self.const_buffer_cpu.light_count = lights.len() as u32;
+
+        // Like the joint buffer, the shader needs a light buffer bound even when there are no lights
+        if lights.is_empty() {
+            lights.push(LightData::default());
+        }
+        self.light_buffer_gpu = Some(self.device.as_ref().unwrap().new_buffer_with_data(
+            lights.as_ptr() as *const std::ffi::c_void,
+            (lights.len() * mem::size_of::<LightData>()) as u64,
+            MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+        ));
+        command_encoder.set_fragment_buffer(0, self.light_buffer_gpu.as_ref().map(|buffer| buffer.as_ref()), 0);
+
+        for entry in &self.model_queue {
+            let model = match self.loaded_models.get_mut(entry.model) {
+                Ok(model) => model,
+                Err(err) => {
+                    println!("Can not draw model {:?}: {err}", entry.model);
+                    continue;
+                }
+            };
+            model.update_world_matrices();
+            let model = self.loaded_models.get(entry.model).unwrap();
+
+            // Every node with a mesh is drawn with its own model matrix.
+            // Skinned meshes ignore the node's transform, the joint matrices already place them in the model.
+            for node_index in model.scene_nodes() {
+                let node = &model.nodes[node_index];
+                let Some(primitives) = node.mesh.and_then(|mesh| model.meshes.get(mesh)) else {
+                    continue;
+                };
+                let (model_matrix, mut joint_matrices) = match node.skin {
+                    Some(skin) => (entry.transform.local_matrix(), model.skins[skin].joint_matrices(&model.nodes)),
+                    None => (entry.transform.local_matrix() * node.world_matrix, Vec::new()),
+                };
+                self.const_buffer_cpu.model_matrix = model_matrix.transpose();
+                self.const_buffer_cpu.normal_matrix = normal_matrix(model_matrix).transpose();
+                self.const_buffer_cpu.joint_count = joint_matrices.len() as u32;
+
+                // The shader needs a joint buffer bound either way, so meshes without a skin get a single identity matrix
+                if joint_matrices.is_empty() {
+                    joint_matrices.push(Mat4::IDENTITY);
+                }
+                let joint_matrices: Vec<Mat4> = joint_matrices.iter().map(|matrix| matrix.transpose()).collect();
+                self.joint_buffer_gpu.push(self.device.as_ref().unwrap().new_buffer_with_data(
+                    joint_matrices.as_ptr() as *const std::ffi::c_void,
+                    (joint_matrices.len() * mem::size_of::<Mat4>()) as u64,
+                    MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+                ));
+                command_encoder.set_vertex_buffer(2, Some(self.joint_buffer_gpu.last().unwrap()), 0);
+
+                self.const_buffer_gpu.push(self.device.as_ref().unwrap().new_buffer_with_data(
+                    &mut self.const_buffer_cpu as *mut _ as *const std::ffi::c_void,
+                    mem::size_of::<ConstBuffer>() as u64,
+                    MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+                ));
+                command_encoder.set_vertex_buffer(1, Some(self.const_buffer_gpu.last().unwrap()), 0);
+                command_encoder.set_fragment_buffer(1, Some(self.const_buffer_gpu.last().unwrap()), 0);
+                Self::update_const_buffer_gpu(self.const_buffer_gpu.last_mut().unwrap(), &self.const_buffer_cpu);
+
+                for primitive in primitives.iter().filter(|primitive| !primitive.mesh.is_empty()) {
+                    let mesh_buffers = match primitive.mesh.buffer.map(|buffer| self.mesh_buffers.get(buffer)) {
+                        Some(Ok(mesh_buffers)) => mesh_buffers,
+                        Some(Err(err)) => {
+                            println!("Can not draw mesh of node {}: {err}", node.name);
+                            continue;
+                        }
+                        None => {
+                            println!("Can not draw mesh of node {}, it was never uploaded", node.name);
+                            continue;
+                        }
+                    };
+                    // Every texture slot has its own sampler, bound at the same index as the texture
+                    let material = model.material(primitive.material);
+                    command_encoder.set_cull_mode(if material.double_sided { MTLCullMode::None } else { MTLCullMode::Back });
+                    for (index, slot) in material.texture_slots().into_iter().enumerate() {
+                        let sampler = slot.map_or(Sampler::new(), |slot| slot.sampler);
+                        let sampler_state = Self::sampler_state(&mut self.sampler_states, self.device.as_ref().unwrap(), sampler);
+                        command_encoder.set_fragment_sampler_state(index as u64, Some(sampler_state));
+                        let texture = self.resolve_texture(slot.map(|slot| slot.texture));
+                        command_encoder.set_fragment_texture(index as u64, Some(texture));
+                    }
+                    let material_data = material.to_material_data();
+                    command_encoder.set_fragment_bytes(
+                        2,
+                        mem::size_of::<MaterialData>() as u64,
+                        &material_data as *const _ as *const std::ffi::c_void,
+                    );
+
+                    // Morph targets are blended on the CPU, and uploaded as a vertex buffer for just this frame
+                    if has_active_weights(&primitive.mesh.morph_targets, &node.weights) {
+                        let morphed_verts = blend_vertices(&primitive.mesh.verts, &primitive.mesh.morph_targets, &node.weights);
+                        self.morph_buffer_gpu.push(self.device.as_ref().unwrap().new_buffer_with_data(
+                            morphed_verts.as_ptr() as *const std::ffi::c_void,
+                            (morphed_verts.len() * mem::size_of::<Vertex>()) as u64,
+                            MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+                        ));
+                        command_encoder.set_vertex_buffer(0, Some(self.morph_buffer_gpu.last().unwrap()), 0);
+                    } else {
+                        command_encoder.set_vertex_buffer(0, Some(&mesh_buffers.vertex_buffer), 0);
+                    }
+                    command_encoder.draw_indexed_primitives(
+                        mesh_buffers.primitive_type,
+                        mesh_buffers.index_count,
+                        mesh_buffers.index_type,
+                        &mesh_buffers.index_buffer,
+                        0,
+                    );
+                }
+            }
+        }
+        command_encoder.end_encoding();
+
+        match drawable {
+            // Present framebuffer
+            Some(drawable) => {
+                command_buffer.present_drawable(drawable);
+                command_buffer.commit();
+            }
+            // Make the offscreen texture readable from the CPU, and wait for the frame to finish so it can be read back
+            None => {
+                let blit_encoder = command_buffer.new_blit_command_encoder();
+                blit_encoder.synchronize_resource(target_texture);
+                blit_encoder.end_encoding();
+                command_buffer.commit();
+                command_buffer.wait_until_completed();
+            }
+        }
+    }
+
+    fn resize_framebuffer(&mut self, width: u32, height: u32) {
+        println!("framebuffer resized to {width}, {height}");
+        self.framebuffer_size = (width, height);
+        if let Some(layer) = self.layer.as_ref() {
+            layer.set_drawable_size(CGSize::new(width as f64, height as f64));
+        } else {
+            let offscreen_texture_desc = TextureDescriptor::new();
+            offscreen_texture_desc.set_width(width as u64);
+            offscreen_texture_desc.set_height(height as u64);
+            offscreen_texture_desc.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+            offscreen_texture_desc.set_usage(MTLTextureUsage::RenderTarget | MTLTextureUsage::ShaderRead);
+            offscreen_texture_desc.set_storage_mode(MTLStorageMode::Managed);
+            self.offscreen_texture = Some(self.device.as_ref().unwrap().new_texture(&offscreen_texture_desc));
+        }
+
+        let depth_texture_desc = TextureDescriptor::new();
+        depth_texture_desc.set_width(width as u64);
+        depth_texture_desc.set_height(height as u64);
+        depth_texture_desc.set_pixel_format(MTLPixelFormat::Depth32Float);
+        self.depth_texture = Some(self.device.as_ref().unwrap().new_texture(&depth_texture_desc));
+    }
+
+    fn draw_model(&mut self, model_queue_entry: ModelQueueEntry) {
+        self.model_queue.push(model_queue_entry);
+    }
+
+    fn framebuffer_size(&self) -> (u32, u32) {
+        self.framebuffer_size
+    }
+
+    fn read_framebuffer(&self) -> Option<Vec<u8>> {
+        // We can only read back our own offscreen texture, the window's drawables are gone after presenting them
+        let texture = self.offscreen_texture.as_ref()?;
+        let (width, height) = self.framebuffer_size;
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        texture.get_bytes(pixels.as_mut_ptr() as _, width as u64 * 4, MTLRegion{
+            origin: MTLOrigin { x: 0, y: 0, z: 0 },
+            size: MTLSize {
+                width: width as u64,
+                height: height as u64,
+                depth: 1,
+            },
+        }, 0);
+        Some(pixels)
+    }
+
+    fn store_model(&mut self, model: Model) -> ModelHandle {
+        self.loaded_models.insert(model)
+    }
+
+    fn model(&self, model: ModelHandle) -> Result<&Model, HandleError> {
+        self.loaded_models.get(model)
+    }
+
+    fn model_mut(&mut self, model: ModelHandle) -> Result<&mut Model, HandleError> {
+        self.loaded_models.get_mut(model)
+    }
+
+    fn unload_model(&mut self, model: ModelHandle) -> Result<(), HandleError> {
+        // Once the model is removed, everything it owned is freed even when one of them fails, and the first error is returned
+        let model = self.loaded_models.remove(model)?;
+        let mut result = Ok(());
+        for primitive in model.meshes.iter().flatten() {
+            if let Some(buffer) = primitive.mesh.buffer {
+                result = result.and(self.mesh_buffers.remove(buffer).map(|_| ()));
+            }
+        }
+        for texture in model.textures() {
+            result = result.and(self.unload_texture(texture));
+        }
+        result
+    }
+
+    fn unload_texture(&mut self, texture: TextureHandle) -> Result<(), HandleError> {
+        self.loaded_textures.remove(texture).map(|_| ())
+    }
+
+    fn update_camera(&mut self, camera_transform: &Transform) {
+        // Update CPU-side buffer
+        self.const_buffer_cpu.view_matrix = camera_transform.view_matrix().transpose();
+        self.const_buffer_cpu.camera_position = camera_transform.translation.extend(1.0);
+        let aspect_ratio = self.framebuffer_size.0.max(1) as f32 / self.framebuffer_size.1.max(1) as f32;
+        self.const_buffer_cpu.proj_matrix = self.projection.matrix(aspect_ratio).transpose();
+    }
+
+    fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    fn set_lights(&mut self, lights: Vec<PlacedLight>) {
+        self.lights = lights;
+    }
+
+    fn upload_texture(&mut self, texture: &mut Texture) -> TextureHandle {
+        let texture_desc = TextureDescriptor::new();
+        texture_desc.set_width(texture.width as u64);
+        texture_desc.set_height(texture.height as u64);
+        texture_desc.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+        // Room for every mip level down to 1x1, which get filled in below
+        let mip_levels = texture.width.max(texture.height).max(1).ilog2() + 1;
+        texture_desc.set_mipmap_level_count(mip_levels as u64);
+
+        let texture_gpu = self.device.as_ref().unwrap().new_texture(&texture_desc);
+        texture_gpu.replace_region(MTLRegion{
+            origin: MTLOrigin { x: 0, y: 0, z: 0 },
+            size: MTLSize {
+                width: texture.width as u64,
+                height: texture.height as u64,
+                depth: 1,
+            },
+        }, 0, texture.data.as_ptr() as _, texture.width as u64 * 4);
+
+        // Generate the mip levels from the base level on the GPU, and wait so the texture is complete before it's sampled
+        let command_buffer = self.command_queue.as_ref().unwrap().new_command_buffer();
+        let blit_encoder = command_buffer.new_blit_command_encoder();
+        blit_encoder.generate_mipmaps(&texture_gpu);
+        blit_encoder.end_encoding();
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+        let handle = self.loaded_textures.insert(texture_gpu);
+        texture.gl_id = handle.index() as u32;
+        return handle;
+    }
+}
\ No newline at end of file
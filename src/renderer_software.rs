@@ -0,0 +1,616 @@
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+
+use crate::camera::Projection;
+use crate::graphics::{ModelQueueEntry, RenderBackend};
+use crate::handle::{HandleError, MeshHandle, ModelHandle, ResourcePool, TextureHandle};
+use crate::helpers::{edge_function, normal_matrix, pack_color, point_inside_triangle, unpack_color};
+use crate::light::PlacedLight;
+use crate::material::{Material, MaterialData};
+use crate::mesh::{Indices, Mesh, Model, PrimitiveType};
+use crate::morph::{blend_vertices, has_active_weights};
+use crate::pbr::{linear_to_srgb, perturb_normal, shade_surface, srgb_to_linear, Surface};
+use crate::skin::skin_vertex;
+use crate::structs::{ConstBuffer, FragIn, Transform, Vertex};
+use crate::sampling::MipChain;
+use crate::texture::{Sampler, Texture};
+
+const CLEAR_COLOR: Vec4 = Vec4::new(0.1, 0.1, 0.2, 1.0);
+
+// The software equivalent of a vertex and index buffer on the GPU
+struct MeshBuffers {
+    verts: Vec<Vertex>,
+    indices: Indices,
+    primitive_type: PrimitiveType,
+}
+
+// The textures and factors a primitive is shaded with
+struct BoundMaterial {
+    textures: [(TextureHandle, Sampler); 5], // Base color, metallic/roughness, normal, occlusion and emissive, white when the material has none
+    data: MaterialData,
+}
+
+// How much the UVs change from one pixel to the next, horizontally and vertically, which picks the mip level to sample.
+// Points and lines don't cover an area, so they leave these at zero and sample the base level.
+#[derive(Clone, Copy, Default)]
+struct UvDerivatives {
+    uv0_dx: Vec2,
+    uv0_dy: Vec2,
+    uv1_dx: Vec2,
+    uv1_dy: Vec2,
+}
+
+// What the rasterizer knows about a pixel, besides the vertex attributes it interpolated
+#[derive(Clone, Copy)]
+struct Fragment {
+    depth: f32,
+    front_facing: bool, // Points and lines are always front facing
+    derivatives: UvDerivatives,
+}
+
+impl Fragment {
+    // A pixel of a point or line
+    fn without_area(depth: f32) -> Fragment {
+        Fragment {
+            depth,
+            front_facing: true,
+            derivatives: UvDerivatives::default(),
+        }
+    }
+}
+
+// Everything needed to draw one primitive of a node
+struct DrawCall {
+    buffer: MeshHandle,
+    material: BoundMaterial,
+    model_matrix: Mat4,
+    joint_matrices: Vec<Mat4>, // Empty if the mesh is not skinned
+    morphed_verts: Option<Vec<Vertex>>, // Replaces the uploaded vertices when the mesh has active morph targets
+}
+
+// Reference renderer that runs entirely on the CPU. It follows the same steps as the Metal pipeline:
+// transform each vertex with the constant buffer matrices, then rasterize the points, lines and triangles into a color and depth buffer.
+pub struct SoftwareRenderer {
+    width: usize,
+    height: usize,
+    color_buffer: Vec<u32>, // Same layout as the texture data: 0xAABBGGRR, so the bytes in memory are RGBA
+    depth_buffer: Vec<f32>,
+    const_buffer_cpu: ConstBuffer,
+    projection: Projection,
+    lights: Vec<PlacedLight>,       // Set with set_lights
+    frame_lights: Vec<PlacedLight>, // Lights used this frame, both the ones above and the ones in the drawn models
+    joint_matrices: Vec<Mat4>, // Of the skin of the mesh that's being drawn, empty if it's not skinned
+    loaded_models: ResourcePool<Model>,
+    loaded_textures: ResourcePool<MipChain, Texture>,
+    mesh_buffers: ResourcePool<MeshBuffers, Mesh>,
+    model_queue: Vec<ModelQueueEntry>,
+    tex_white: Option<TextureHandle>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        // Initialize renderer with empty buffers
+        let mut renderer = SoftwareRenderer {
+            width: 0,
+            height: 0,
+            color_buffer: Vec::new(),
+            depth_buffer: Vec::new(),
+            const_buffer_cpu: ConstBuffer{
+                model_matrix: Mat4::IDENTITY,
+                view_matrix: Mat4::IDENTITY,
+                proj_matrix: Mat4::IDENTITY,
+                normal_matrix: Mat4::IDENTITY,
+                joint_count: 0,
+                light_count: 0,
+                _padding: [0; 2],
+                camera_position: Vec4::W,
+            },
+            projection: Projection::default(),
+            lights: Vec::new(),
+            frame_lights: Vec::new(),
+            joint_matrices: Vec::new(),
+            loaded_models: ResourcePool::new(),
+            loaded_textures: ResourcePool::new(),
+            mesh_buffers: ResourcePool::new(),
+            model_queue: Vec::new(),
+            tex_white: None,
+        };
+
+        // Allocate the framebuffer
+        renderer.resize_framebuffer(width, height);
+
+        // Initialize default white texture
+        let mut tex_white = Texture {
+            gl_id: 0,
+            width: 1,
+            height: 1,
+            depth: 1,
+            data: vec![0xFFFFFFFFu32]
+        };
+        renderer.tex_white = Some(renderer.upload_texture(&mut tex_white));
+
+        return renderer;
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // The color buffer from the last frame, one 0xAABBGGRR pixel per element, top row first
+    pub fn framebuffer(&self) -> &[u32] {
+        &self.color_buffer
+    }
+
+    // The color buffer from the last frame as tightly packed RGBA8 bytes, top row first
+    pub fn framebuffer_rgba8(&self) -> Vec<u8> {
+        self.color_buffer.iter().flat_map(|pixel| pixel.to_le_bytes()).collect()
+    }
+
+    fn clear(&mut self) {
+        self.color_buffer.fill(pack_color(CLEAR_COLOR));
+        self.depth_buffer.fill(1.0);
+    }
+
+    fn vertex_shader(&self, vertex: &Vertex) -> FragIn {
+        let vertex = if self.joint_matrices.is_empty() { *vertex } else { skin_vertex(vertex, &self.joint_matrices) };
+        let mvp = self.const_buffer_cpu.proj_matrix * self.const_buffer_cpu.view_matrix * self.const_buffer_cpu.model_matrix;
+        FragIn {
+            position: mvp * vertex.position.extend(1.0),
+            world_position: self.const_buffer_cpu.model_matrix.transform_point3(vertex.position),
+            normal: self.const_buffer_cpu.normal_matrix.transform_vector3(vertex.normal),
+            tangent: self.const_buffer_cpu.model_matrix.transform_vector3(vertex.tangent.xyz()).extend(vertex.tangent.w),
+            color: vertex.color,
+            uv0: vertex.uv0,
+            uv1: vertex.uv1,
+        }
+    }
+
+    // Metallic-roughness shading, the same as hello_triangle_fragment in hello_triangle.metal. Without any lights, the base color is drawn unlit.
+    // Returns None when the pixel is discarded by the material's alpha cutoff.
+    fn fragment_shader(input: &FragIn, fragment: Fragment, textures: [(&MipChain, Sampler); 5], material: &MaterialData, lights: &[PlacedLight], camera_position: Vec3) -> Option<Vec4> {
+        let [tex_alb, tex_mtl_rgh, tex_nrm, tex_occ, tex_emm] = textures;
+        let derivatives = fragment.derivatives;
+        let uv = |tex_coord: u32| match tex_coord {
+            1 => (input.uv1, derivatives.uv1_dx, derivatives.uv1_dy),
+            _ => (input.uv0, derivatives.uv0_dx, derivatives.uv0_dy),
+        };
+        let albedo = sample_texture(tex_alb, uv(material.tex_coords.x));
+        let base_color = input.color * srgb_to_linear(albedo.xyz()).extend(albedo.w) * material.base_color;
+
+        // Only blended materials keep their alpha, masked ones are opaque where they aren't cut out
+        let alpha = match material.alpha_mode {
+            1 if base_color.w < material.alpha_cutoff => return None,
+            2 => base_color.w,
+            _ => 1.0,
+        };
+        if lights.is_empty() {
+            return Some(linear_to_srgb(base_color.xyz()).extend(alpha));
+        }
+
+        // The back faces of double sided materials face the other way
+        let (normal, tangent) = match fragment.front_facing || material.double_sided == 0 {
+            true => (input.normal, input.tangent),
+            false => (-input.normal, -input.tangent),
+        };
+
+        // Roughness is in the green channel and metallic in the blue one, so they can share a texture with occlusion in red
+        let metallic_roughness = sample_texture(tex_mtl_rgh, uv(material.tex_coords.y));
+        let normal_sample = sample_texture(tex_nrm, uv(material.tex_coords.z)).xyz();
+        let occlusion = sample_texture(tex_occ, uv(material.tex_coords.w)).x;
+        let emissive = srgb_to_linear(sample_texture(tex_emm, uv(material.tex_coord_emm)).xyz()) * material.emissive.xyz();
+        let surface = Surface {
+            base_color: base_color.xyz(),
+            metallic: (material.metallic * metallic_roughness.z).clamp(0.0, 1.0),
+            roughness: (material.roughness * metallic_roughness.y).clamp(0.0, 1.0),
+            normal: perturb_normal(normal, tangent, normal_sample, material.normal_scale),
+            occlusion: 1.0 + material.occlusion_strength * (occlusion - 1.0),
+            emissive,
+        };
+        let to_view = (camera_position - input.world_position).normalize_or_zero();
+        let color = shade_surface(lights, input.world_position, to_view, &surface);
+        return Some(linear_to_srgb(color).extend(alpha));
+    }
+
+    // Resolves every texture of a material, so a draw call doesn't have to look them up again
+    fn bind_material(&self, material: &Material) -> BoundMaterial {
+        BoundMaterial {
+            textures: material.texture_slots().map(|slot| {
+                let sampler = slot.map_or(Sampler::new(), |slot| slot.sampler);
+                (self.resolve_texture(slot.map(|slot| slot.texture)), sampler)
+            }),
+            data: material.to_material_data(),
+        }
+    }
+
+    // Returns the texture to bind for a material slot, falling back to white when there is none or the handle is invalid
+    fn resolve_texture(&self, texture: Option<TextureHandle>) -> TextureHandle {
+        match texture {
+            Some(texture) => match self.loaded_textures.get(texture) {
+                Ok(_) => texture,
+                Err(err) => {
+                    println!("Can not bind texture {texture:?}: {err}");
+                    self.tex_white.unwrap()
+                }
+            },
+            None => self.tex_white.unwrap(),
+        }
+    }
+
+    // Perspective divide and viewport transform, returns the pixel position and depth
+    fn viewport_transform(&self, position: Vec4) -> Vec3 {
+        let ndc = position.xyz() / position.w;
+        Vec3::new(
+            (ndc.x * 0.5 + 0.5) * self.width as f32,
+            (0.5 - ndc.y * 0.5) * self.height as f32,
+            ndc.z,
+        )
+    }
+
+    // Depth tests a single pixel, and runs the fragment shader for it if it passes.
+    // The result is blended over the color buffer with source-over blending, like the Metal pipeline does.
+    fn shade_pixel(&mut self, x: usize, y: usize, fragment: Fragment, frag_in: &FragIn, material: &BoundMaterial) {
+        let index = x + y * self.width;
+        if fragment.depth >= self.depth_buffer[index] {
+            return;
+        }
+        let textures = material.textures.map(|(texture, sampler)| (self.loaded_textures.get(texture).unwrap(), sampler));
+        let camera_position = self.const_buffer_cpu.camera_position.xyz();
+        let Some(color) = Self::fragment_shader(frag_in, fragment, textures, &material.data, &self.frame_lights, camera_position) else {
+            return;
+        };
+        let destination = unpack_color(self.color_buffer[index]);
+        let rgb = color.xyz() * color.w + destination.xyz() * (1.0 - color.w);
+        let alpha = color.w + destination.w * (1.0 - color.w);
+        self.depth_buffer[index] = fragment.depth;
+        self.color_buffer[index] = pack_color(rgb.extend(alpha));
+    }
+
+    // Points are drawn as a single pixel, like Metal does with a point size of 1
+    fn draw_point(&mut self, point: FragIn, material: &BoundMaterial) {
+        if point.position.z < 0.0 || point.position.w <= 0.0 {
+            return;
+        }
+        let screen = self.viewport_transform(point.position);
+        if screen.x < 0.0 || screen.y < 0.0 || screen.x >= self.width as f32 || screen.y >= self.height as f32 {
+            return;
+        }
+        self.shade_pixel(screen.x as usize, screen.y as usize, Fragment::without_area(screen.z), &point, material);
+    }
+
+    fn draw_line(&mut self, line: [FragIn; 2], material: &BoundMaterial) {
+        // Clip against the near plane
+        let [mut start, mut end] = line;
+        match (start.position.z >= 0.0, end.position.z >= 0.0) {
+            (false, false) => return,
+            (true, false) => end = start.lerp(end, start.position.z / (start.position.z - end.position.z)),
+            (false, true) => start = end.lerp(start, end.position.z / (end.position.z - start.position.z)),
+            (true, true) => {}
+        }
+
+        // Step along the longest axis, one pixel at a time
+        let screen_start = self.viewport_transform(start.position);
+        let screen_end = self.viewport_transform(end.position);
+        let steps = (screen_end.x - screen_start.x).abs().max((screen_end.y - screen_start.y).abs()).ceil().max(1.0) as usize;
+        let (inv_w_start, inv_w_end) = (1.0 / start.position.w, 1.0 / end.position.w);
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let screen = screen_start.lerp(screen_end, t);
+            if screen.x < 0.0 || screen.y < 0.0 || screen.x >= self.width as f32 || screen.y >= self.height as f32 {
+                continue;
+            }
+
+            // Perspective correct attribute interpolation
+            let t_perspective = t * inv_w_end / ((1.0 - t) * inv_w_start + t * inv_w_end);
+            let frag_in = start.lerp(end, t_perspective);
+            self.shade_pixel(screen.x as usize, screen.y as usize, Fragment::without_area(screen.z), &frag_in, material);
+        }
+    }
+
+    fn draw_triangle(&mut self, triangle: [FragIn; 3], material: &BoundMaterial) {
+        // Clip against the near plane, which can turn the triangle into a polygon with up to 4 vertices
+        let polygon = clip_near_plane(&triangle);
+        if polygon.len() < 3 {
+            return;
+        }
+
+        // Then draw that polygon as a triangle fan
+        for i in 1..polygon.len() - 1 {
+            self.rasterize_triangle([polygon[0], polygon[i], polygon[i + 1]], material);
+        }
+    }
+
+    fn rasterize_triangle(&mut self, triangle: [FragIn; 3], material: &BoundMaterial) {
+        // Perspective divide and viewport transform
+        let screen = triangle.map(|vertex| self.viewport_transform(vertex.position));
+        let inv_w = triangle.map(|vertex| 1.0 / vertex.position.w);
+
+        // Triangles are front facing when they're counter-clockwise, which is clockwise on screen since Y points down.
+        // Back faces are only drawn for double sided materials, with the winding order flipped to be consistent.
+        let (mut v0, mut v1, v2) = (screen[0], screen[1], screen[2]);
+        let (mut i0, mut i1, i2) = (0, 1, 2);
+        let mut area = edge_function(v0.truncate(), v1.truncate(), v2.truncate());
+        let front_facing = area > 0.0;
+        if !front_facing && material.data.double_sided == 0 {
+            return;
+        }
+        if area < 0.0 {
+            std::mem::swap(&mut v0, &mut v1);
+            std::mem::swap(&mut i0, &mut i1);
+            area = -area;
+        }
+        if area == 0.0 {
+            return;
+        }
+
+        // Find the bounding box of the triangle on screen
+        let min = v0.min(v1).min(v2).truncate().max(Vec2::ZERO);
+        let max = v0.max(v1).max(v2).truncate().min(Vec2::new(self.width as f32, self.height as f32));
+        if min.x >= max.x || min.y >= max.y {
+            return;
+        }
+
+        // Screen space barycentric coordinates, and the perspective correct weights to interpolate the attributes with.
+        // This also works for points outside of the triangle, which the UV derivatives need near its edges.
+        let weights_at = |p: Vec2| {
+            let b0 = edge_function(v1.truncate(), v2.truncate(), p) / area;
+            let b1 = edge_function(v2.truncate(), v0.truncate(), p) / area;
+            let b2 = edge_function(v0.truncate(), v1.truncate(), p) / area;
+            let weights = Vec3::new(b0 * inv_w[i0], b1 * inv_w[i1], b2 * inv_w[i2]);
+            (Vec3::new(b0, b1, b2), weights / (weights.x + weights.y + weights.z))
+        };
+        let uvs_at = |weights: Vec3| {
+            let uv0 = triangle[i0].uv0 * weights.x + triangle[i1].uv0 * weights.y + triangle[i2].uv0 * weights.z;
+            let uv1 = triangle[i0].uv1 * weights.x + triangle[i1].uv1 * weights.y + triangle[i2].uv1 * weights.z;
+            (uv0, uv1)
+        };
+
+        for y in (min.y as usize)..(max.y.ceil() as usize) {
+            for x in (min.x as usize)..(max.x.ceil() as usize) {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if !point_inside_triangle(v0.truncate(), v1.truncate(), v2.truncate(), p) {
+                    continue;
+                }
+
+                let (barycentric, weights) = weights_at(p);
+                let depth = barycentric.dot(Vec3::new(v0.z, v1.z, v2.z));
+                let frag_in = FragIn::barycentric(&triangle[i0], &triangle[i1], &triangle[i2], weights);
+
+                // Finite differences with the pixels to the right and below, like a GPU does for a 2x2 quad of pixels
+                let (uv0, uv1) = (frag_in.uv0, frag_in.uv1);
+                let (uv0_right, uv1_right) = uvs_at(weights_at(p + Vec2::X).1);
+                let (uv0_below, uv1_below) = uvs_at(weights_at(p + Vec2::Y).1);
+                let derivatives = UvDerivatives {
+                    uv0_dx: uv0_right - uv0,
+                    uv0_dy: uv0_below - uv0,
+                    uv1_dx: uv1_right - uv1,
+                    uv1_dy: uv1_below - uv1,
+                };
+                self.shade_pixel(x, y, Fragment { depth, front_facing, derivatives }, &frag_in, material);
+            }
+        }
+    }
+}
+
+impl RenderBackend for SoftwareRenderer {
+    fn prepare_pipeline_state(&mut self, _vertex_shader_path: &str, _fragment_shader_path: &str) {
+        // The shading is built into the software renderer, so there is nothing to prepare
+    }
+
+    fn upload_vertex_buffer(&mut self, mesh: &mut Mesh) {
+        // Empty meshes aren't uploaded, the same as in the Metal renderer
+        if mesh.is_empty() {
+            return;
+        }
+        mesh.buffer = Some(self.mesh_buffers.insert(MeshBuffers {
+            verts: mesh.verts.clone(),
+            indices: mesh.indices.clone(),
+            primitive_type: mesh.primitive_type,
+        }));
+    }
+
+    fn upload_texture(&mut self, texture: &mut Texture) -> TextureHandle {
+        let handle = self.loaded_textures.insert(MipChain::new(texture));
+        texture.gl_id = handle.index() as u32;
+        return handle;
+    }
+
+    fn begin_frame(&mut self) {
+        self.model_queue.clear();
+    }
+
+    fn draw_model(&mut self, model_queue_entry: ModelQueueEntry) {
+        self.model_queue.push(model_queue_entry);
+    }
+
+    fn end_frame(&mut self) {
+        self.clear();
+
+        let model_queue = std::mem::take(&mut self.model_queue);
+
+        // Gather the lights of every model first, so they also light the models drawn before them
+        self.frame_lights = self.lights.clone();
+        for entry in &model_queue {
+            if let Ok(model) = self.loaded_models.get_mut(entry.model) {
+                model.update_world_matrices();
+                self.frame_lights.extend(model.placed_lights(entry.transform.local_matrix()));
+            }
+        }
+        self.const_buffer_cpu.light_count = self.frame_lights.len() as u32;
+
+        for entry in &model_queue {
+            let model = match self.loaded_models.get_mut(entry.model) {
+                Ok(model) => model,
+                Err(err) => {
+                    println!("Can not draw model {:?}: {err}", entry.model);
+                    continue;
+                }
+            };
+            model.update_world_matrices();
+            let model = self.loaded_models.get(entry.model).unwrap();
+
+            // Every node with a mesh is drawn with its own model matrix.
+            // Skinned meshes ignore the node's transform, the joint matrices already place them in the model.
+            let mut draw_calls: Vec<DrawCall> = Vec::new();
+            for node_index in model.scene_nodes() {
+                let node = &model.nodes[node_index];
+                let Some(primitives) = node.mesh.and_then(|mesh| model.meshes.get(mesh)) else {
+                    continue;
+                };
+                let (model_matrix, joint_matrices) = match node.skin {
+                    Some(skin) => (entry.transform.local_matrix(), model.skins[skin].joint_matrices(&model.nodes)),
+                    None => (entry.transform.local_matrix() * node.world_matrix, Vec::new()),
+                };
+                for primitive in primitives.iter().filter(|primitive| !primitive.mesh.is_empty()) {
+                    let Some(buffer) = primitive.mesh.buffer else {
+                        println!("Can not draw mesh of node {}, it was never uploaded", node.name);
+                        continue;
+                    };
+                    let material = self.bind_material(model.material(primitive.material));
+                    let morphed_verts = has_active_weights(&primitive.mesh.morph_targets, &node.weights)
+                        .then(|| blend_vertices(&primitive.mesh.verts, &primitive.mesh.morph_targets, &node.weights));
+                    draw_calls.push(DrawCall {
+                        buffer,
+                        material,
+                        model_matrix,
+                        joint_matrices: joint_matrices.clone(),
+                        morphed_verts,
+                    });
+                }
+            }
+
+            for draw_call in draw_calls {
+                let (buffer, material) = (draw_call.buffer, &draw_call.material);
+                self.const_buffer_cpu.model_matrix = draw_call.model_matrix;
+                self.const_buffer_cpu.normal_matrix = normal_matrix(draw_call.model_matrix);
+                self.const_buffer_cpu.joint_count = draw_call.joint_matrices.len() as u32;
+                self.joint_matrices = draw_call.joint_matrices;
+
+                // Run the vertex shader once per vertex, then assemble the primitives from the index buffer
+                let (shaded_verts, indices, primitive_type) = match self.mesh_buffers.get(buffer) {
+                    Ok(mesh_buffers) => (
+                        draw_call.morphed_verts.as_ref().unwrap_or(&mesh_buffers.verts)
+                            .iter()
+                            .map(|vertex| self.vertex_shader(vertex))
+                            .collect::<Vec<FragIn>>(),
+                        mesh_buffers.indices.to_u32(),
+                        mesh_buffers.primitive_type,
+                    ),
+                    Err(err) => {
+                        println!("Can not draw mesh {buffer:?}: {err}");
+                        continue;
+                    }
+                };
+                match primitive_type {
+                    PrimitiveType::Points => {
+                        for index in indices {
+                            self.draw_point(shaded_verts[index as usize], material);
+                        }
+                    }
+                    PrimitiveType::Lines => {
+                        for line in indices.chunks_exact(2) {
+                            self.draw_line([shaded_verts[line[0] as usize], shaded_verts[line[1] as usize]], material);
+                        }
+                    }
+                    PrimitiveType::Triangles => {
+                        for triangle in indices.chunks_exact(3) {
+                            let triangle = [
+                                shaded_verts[triangle[0] as usize],
+                                shaded_verts[triangle[1] as usize],
+                                shaded_verts[triangle[2] as usize],
+                            ];
+                            self.draw_triangle(triangle, material);
+                        }
+                    }
+                }
+            }
+        }
+        self.model_queue = model_queue;
+    }
+
+    fn resize_framebuffer(&mut self, width: u32, height: u32) {
+        self.width = width as usize;
+        self.height = height as usize;
+        self.color_buffer = vec![pack_color(CLEAR_COLOR); self.width * self.height];
+        self.depth_buffer = vec![1.0; self.width * self.height];
+    }
+
+    fn update_camera(&mut self, camera_transform: &Transform) {
+        let aspect_ratio = self.width.max(1) as f32 / self.height.max(1) as f32;
+        self.const_buffer_cpu.view_matrix = camera_transform.view_matrix();
+        self.const_buffer_cpu.camera_position = camera_transform.translation.extend(1.0);
+        self.const_buffer_cpu.proj_matrix = self.projection.matrix(aspect_ratio);
+    }
+
+    fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    fn set_lights(&mut self, lights: Vec<PlacedLight>) {
+        self.lights = lights;
+    }
+
+    fn framebuffer_size(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+
+    fn read_framebuffer(&self) -> Option<Vec<u8>> {
+        Some(self.framebuffer_rgba8())
+    }
+
+    fn store_model(&mut self, model: Model) -> ModelHandle {
+        self.loaded_models.insert(model)
+    }
+
+    fn model(&self, model: ModelHandle) -> Result<&Model, HandleError> {
+        self.loaded_models.get(model)
+    }
+
+    fn model_mut(&mut self, model: ModelHandle) -> Result<&mut Model, HandleError> {
+        self.loaded_models.get_mut(model)
+    }
+
+    fn unload_model(&mut self, model: ModelHandle) -> Result<(), HandleError> {
+        // Once the model is removed, everything it owned is freed even when one of them fails, and the first error is returned
+        let model = self.loaded_models.remove(model)?;
+        let mut result = Ok(());
+        for primitive in model.meshes.iter().flatten() {
+            if let Some(buffer) = primitive.mesh.buffer {
+                result = result.and(self.mesh_buffers.remove(buffer).map(|_| ()));
+            }
+        }
+        for texture in model.textures() {
+            result = result.and(self.unload_texture(texture));
+        }
+        result
+    }
+
+    fn unload_texture(&mut self, texture: TextureHandle) -> Result<(), HandleError> {
+        self.loaded_textures.remove(texture).map(|_| ())
+    }
+}
+
+// Sutherland-Hodgman clipping against the near plane (z >= 0 in clip space)
+fn clip_near_plane(triangle: &[FragIn; 3]) -> Vec<FragIn> {
+    let mut output = Vec::with_capacity(4);
+    for i in 0..3 {
+        let curr = triangle[i];
+        let next = triangle[(i + 1) % 3];
+        let curr_inside = curr.position.z >= 0.0;
+        let next_inside = next.position.z >= 0.0;
+        if curr_inside {
+            output.push(curr);
+        }
+        if curr_inside != next_inside {
+            let t = curr.position.z / (curr.position.z - next.position.z);
+            output.push(curr.lerp(next, t));
+        }
+    }
+    output
+}
+
+fn sample_texture((mips, sampler): (&MipChain, Sampler), (uv, uv_dx, uv_dy): (Vec2, Vec2, Vec2)) -> Vec4 {
+    sampler.sample(mips, uv, uv_dx, uv_dy)
+}
@@ -0,0 +1,150 @@
+use glam::{UVec2, Vec2, Vec4};
+
+use crate::helpers::{pack_color, unpack_color};
+use crate::texture::{FilterMode, Sampler, Texture, WrapMode};
+
+// A texture and all of its mip levels, each half the size of the previous one, down to 1x1
+#[derive(Clone)]
+pub struct MipChain {
+    pub levels: Vec<Texture>,
+}
+
+impl MipChain {
+    // Every level averages 2x2 texels of the one above it, as they're stored, like glGenerateMipmap does.
+    // An empty texture has nothing to average, so it only gets the base level.
+    pub fn new(texture: &Texture) -> Self {
+        let mut levels = vec![texture.clone()];
+        while let Some(level) = levels.last().filter(|level| !level.is_empty() && (level.width > 1 || level.height > 1)) {
+            levels.push(downsample(level));
+        }
+        MipChain { levels }
+    }
+
+    // Only the base level, which is sampled as if it's always magnified
+    pub fn without_mips(texture: &Texture) -> Self {
+        MipChain { levels: vec![texture.clone()] }
+    }
+
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.levels[0].width as u32, self.levels[0].height as u32)
+    }
+}
+
+// Halves a texture. When a side is odd, the last row or column is only averaged with itself, so it's weighted more.
+fn downsample(texture: &Texture) -> Texture {
+    let width = (texture.width / 2).max(1);
+    let height = (texture.height / 2).max(1);
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Vec4::ZERO;
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let source_x = (x * 2 + dx).min(texture.width - 1);
+                let source_y = (y * 2 + dy).min(texture.height - 1);
+                sum += unpack_color(texture.data[source_x + source_y * texture.width]);
+            }
+            data.push(pack_color(sum / 4.0));
+        }
+    }
+    Texture {
+        gl_id: 0,
+        width,
+        height,
+        depth: texture.depth,
+        data,
+    }
+}
+
+impl WrapMode {
+    // Maps a texel index outside of 0..size back onto the texture. An empty axis has no texel to map onto, so it's 0.
+    pub fn apply(self, index: isize, size: usize) -> usize {
+        if size == 0 {
+            return 0;
+        }
+        let size = size as isize;
+        let index = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Mirror => {
+                // Every other repetition is flipped
+                let index = index.rem_euclid(size * 2);
+                if index < size { index } else { size * 2 - 1 - index }
+            }
+            WrapMode::Clamp => index.clamp(0, size - 1),
+        };
+        index as usize
+    }
+}
+
+// How many texels of the base level one pixel covers, as a power of two: 0 is one texel, 1 is two texels, and so on.
+// uv_dx and uv_dy are how much the UV changes from one pixel to the next, horizontally and vertically.
+pub fn level_of_detail(size: UVec2, uv_dx: Vec2, uv_dy: Vec2) -> f32 {
+    let size = size.as_vec2();
+    let footprint = (uv_dx * size).length().max((uv_dy * size).length());
+    footprint.log2()
+}
+
+impl Sampler {
+    // Filters the texture the way a GPU would for a pixel with the given UV derivatives: the magnification filter when
+    // a texel covers more than a pixel, otherwise the minification filter, on the mip levels picked by the mipmap filter.
+    // Textures without mip levels, or samplers without mipmapping, always sample the base level.
+    pub fn sample(&self, mips: &MipChain, uv: Vec2, uv_dx: Vec2, uv_dy: Vec2) -> Vec4 {
+        let lod = level_of_detail(mips.size(), uv_dx, uv_dy);
+        if lod.is_nan() || lod <= 0.0 {
+            return self.sample_level(&mips.levels[0], uv, self.filter_mode_mag);
+        }
+        if !self.mipmap_enabled {
+            return self.sample_level(&mips.levels[0], uv, self.filter_mode_min);
+        }
+
+        let last_level = (mips.levels.len() - 1) as f32;
+        let lod = lod.min(last_level);
+        match self.filter_mode_mipmap {
+            FilterMode::Point => {
+                let level = &mips.levels[lod.round() as usize];
+                return self.sample_level(level, uv, self.filter_mode_min);
+            }
+            FilterMode::Linear => {
+                // Trilinear filtering, when the minification filter is linear too
+                let upper = self.sample_level(&mips.levels[lod.floor() as usize], uv, self.filter_mode_min);
+                let lower = self.sample_level(&mips.levels[lod.ceil() as usize], uv, self.filter_mode_min);
+                return upper.lerp(lower, lod.fract());
+            }
+        }
+    }
+
+    // Samples a single mip level with the given filter, wrapping the UV with the sampler's wrap modes.
+    // UVs that are infinite or NaN, or that overflow once scaled to texels, don't land on any texel, so they sample
+    // transparent black.
+    pub fn sample_level(&self, texture: &Texture, uv: Vec2, filter: FilterMode) -> Vec4 {
+        let position = uv * Vec2::new(texture.width as f32, texture.height as f32);
+        if !position.is_finite() {
+            return Vec4::ZERO;
+        }
+        match filter {
+            FilterMode::Point => {
+                return self.texel(texture, position.x.floor() as isize, position.y.floor() as isize);
+            }
+            FilterMode::Linear => {
+                // Blend the 4 texels around the sample, their centers are at half texel offsets
+                let position = position - Vec2::splat(0.5);
+                // Huge UVs turn into the largest index, which has no next index
+                let (x, y) = (position.x.floor() as isize, position.y.floor() as isize);
+                let (next_x, next_y) = (x.saturating_add(1), y.saturating_add(1));
+                let fraction = position - position.floor();
+                let top = self.texel(texture, x, y).lerp(self.texel(texture, next_x, y), fraction.x);
+                let bottom = self.texel(texture, x, next_y).lerp(self.texel(texture, next_x, next_y), fraction.x);
+                return top.lerp(bottom, fraction.y);
+            }
+        }
+    }
+
+    // A single texel in 0..1 RGBA, the coordinates can be outside of the texture. Empty textures are transparent black.
+    pub fn texel(&self, texture: &Texture, x: isize, y: isize) -> Vec4 {
+        if texture.is_empty() {
+            return Vec4::ZERO;
+        }
+        let x = self.wrap_mode_s.apply(x, texture.width);
+        let y = self.wrap_mode_t.apply(y, texture.height);
+        unpack_color(texture.data[x + y * texture.width])
+    }
+}
@@ -0,0 +1,128 @@
+use glam::{Mat4, Quat, Vec3};
+
+use crate::error::AssetError;
+use crate::mesh::Model;
+use crate::structs::Transform;
+
+// A node in the scene graph of a model. Nodes refer to each other and to meshes by index.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,  // Relative to the parent node
+    pub children: Vec<usize>,  // Indices into Model::nodes
+    pub mesh: Option<usize>,   // Index into Model::meshes
+    pub skin: Option<usize>,   // Index into Model::skins, when set the mesh is deformed by the skin's joints
+    pub camera: Option<usize>, // Index into Model::cameras
+    pub light: Option<usize>,  // Index into Model::lights
+    pub weights: Vec<f32>,     // Morph target weights, animations can change these
+    pub world_matrix: Mat4,    // Relative to the model, updated by Model::update_world_matrices
+}
+
+// A set of root nodes to draw. A file can have several, for example one per level, but only one is drawn at a time.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub name: String,
+    pub root_nodes: Vec<usize>, // Indices into Model::nodes
+}
+
+pub(crate) fn load_scenes(document: &gltf::Document) -> Vec<Scene> {
+    document
+        .scenes()
+        .map(|scene| Scene {
+            name: String::from(scene.name().unwrap_or("untitled")),
+            root_nodes: scene.nodes().map(|node| node.index()).collect(),
+        })
+        .collect()
+}
+
+// Loads every node of the document. Each node may only have one parent, and the roots of a scene can't have one,
+// so walking the tree from the roots never loops forever.
+pub(crate) fn load_nodes(document: &gltf::Document) -> Result<Vec<Node>, AssetError> {
+    let mut nodes = Vec::new();
+    let mut has_parent = vec![false; document.nodes().len()];
+
+    for node in document.nodes() {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let children: Vec<usize> = node.children().map(|child| child.index()).collect();
+        for child in &children {
+            if has_parent[*child] {
+                return Err(AssetError::MalformedData(format!("node {child} has more than one parent")));
+            }
+            has_parent[*child] = true;
+        }
+
+        nodes.push(Node {
+            name: String::from(node.name().unwrap_or("untitled")),
+            transform: Transform {
+                translation: Vec3::from(translation),
+                rotation: Quat::from_array(rotation),
+                scale: Vec3::from(scale),
+            },
+            children,
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            skin: node.skin().map(|skin| skin.index()),
+            camera: node.camera().map(|camera| camera.index()),
+            light: node.light().map(|light| light.index()),
+            // Nodes can override the default weights of their mesh
+            weights: node
+                .weights()
+                .or(node.mesh().and_then(|mesh| mesh.weights()))
+                .map(|weights| weights.to_vec())
+                .unwrap_or_default(),
+            world_matrix: Mat4::IDENTITY,
+        });
+    }
+
+    for scene in document.scenes() {
+        if let Some(root) = scene.nodes().find(|node| has_parent[node.index()]) {
+            return Err(AssetError::MalformedData(format!(
+                "node {} is a root of scene {}, but also has a parent",
+                root.index(),
+                scene.index()
+            )));
+        }
+    }
+    Ok(nodes)
+}
+
+impl Model {
+    // Switches to drawing another scene of the file. Returns false and keeps the current scene if it doesn't exist.
+    pub fn set_scene(&mut self, scene: usize) -> bool {
+        let Some(root_nodes) = self.scenes.get(scene).map(|scene| scene.root_nodes.clone()) else {
+            return false;
+        };
+        self.scene = Some(scene);
+        self.root_nodes = root_nodes;
+        self.update_world_matrices();
+        return true;
+    }
+
+    pub fn find_scene(&self, name: &str) -> Option<usize> {
+        self.scenes.iter().position(|scene| scene.name == name)
+    }
+
+    // Recalculates the world matrix of every node in the scene. Call this after changing node transforms.
+    pub fn update_world_matrices(&mut self) {
+        let mut stack: Vec<(usize, Mat4)> = self.root_nodes.iter().map(|root| (*root, Mat4::IDENTITY)).collect();
+        while let Some((index, parent_matrix)) = stack.pop() {
+            let node = &mut self.nodes[index];
+            node.world_matrix = parent_matrix * node.transform.local_matrix();
+            stack.extend(node.children.iter().map(|child| (*child, node.world_matrix)));
+        }
+    }
+
+    // Every node in the scene, parents before their children
+    pub fn scene_nodes(&self) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut stack: Vec<usize> = self.root_nodes.iter().rev().copied().collect();
+        while let Some(index) = stack.pop() {
+            result.push(index);
+            stack.extend(self.nodes[index].children.iter().rev());
+        }
+        return result;
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+}
@@ -0,0 +1,62 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::graphics::RenderBackend;
+
+// Saves the last rendered frame of a renderer to disk, see save_image for the supported formats
+pub fn save_framebuffer(renderer: &dyn RenderBackend, path: &Path) -> std::io::Result<()> {
+    let (width, height) = renderer.framebuffer_size();
+    let pixels = match renderer.read_framebuffer() {
+        Some(pixels) => pixels,
+        None => return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "this renderer can not read back its framebuffer")),
+    };
+    save_image(path, width, height, &pixels)
+}
+
+// Saves tightly packed RGBA8 pixels, top row first. The file format is picked from the extension: .ppm, or PNG for anything else
+pub fn save_image(path: &Path, width: u32, height: u32, pixels: &[u8]) -> std::io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("ppm") => save_ppm(file, width, height, pixels),
+        _ => save_png(file, width, height, pixels),
+    }
+}
+
+fn save_png(writer: impl Write, width: u32, height: u32, pixels: &[u8]) -> std::io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut png_writer = encoder.write_header()?;
+    png_writer.write_image_data(pixels)?;
+    png_writer.finish()?;
+    Ok(())
+}
+
+fn save_ppm(mut writer: impl Write, width: u32, height: u32, pixels: &[u8]) -> std::io::Result<()> {
+    // PPM has no alpha channel, so only write RGB
+    write!(writer, "P6\n{width} {height}\n255\n")?;
+    for pixel in pixels.chunks_exact(4) {
+        writer.write_all(&pixel[0..3])?;
+    }
+    writer.flush()
+}
+
+// Loads a PNG as tightly packed RGBA8 pixels, top row first. Returns (width, height, pixels)
+pub fn load_png(path: &Path) -> std::io::Result<(u32, u32, Vec<u8>)> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0u8; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "only 8-bit PNG files are supported"));
+    }
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+        _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "only RGB and RGBA PNG files are supported")),
+    };
+    Ok((info.width, info.height, pixels))
+}
@@ -0,0 +1,98 @@
+use glam::{Mat4, Vec4Swizzles};
+
+use crate::accessor::read_accessor_f32;
+use crate::error::AssetError;
+use crate::mesh::Model;
+use crate::scene::Node;
+use crate::structs::Vertex;
+
+// The joints that deform a skinned mesh. Joints are regular nodes, so moving or animating them moves the skin along.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub name: String,
+    pub joints: Vec<usize>,               // Indices into Model::nodes, a vertex's joint indices index into this
+    pub inverse_bind_matrices: Vec<Mat4>, // Transforms the mesh into the local space of each joint, one per joint
+}
+
+impl Skin {
+    // Calculates the skinning matrix of every joint from the current world matrices of the nodes.
+    // The result transforms a vertex from the bind pose to where the joint moved it, in model space.
+    pub fn joint_matrices(&self, nodes: &[Node]) -> Vec<Mat4> {
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(joint, inverse_bind_matrix)| nodes[*joint].world_matrix * *inverse_bind_matrix)
+            .collect()
+    }
+}
+
+// CPU reference implementation of linear blend skinning, the Metal vertex shader does the same thing
+pub fn skin_vertex(vertex: &Vertex, joint_matrices: &[Mat4]) -> Vertex {
+    let mut skin_matrix = Mat4::ZERO;
+    for i in 0..4 {
+        if vertex.weights[i] != 0.0 {
+            skin_matrix += joint_matrices[vertex.joints[i] as usize] * vertex.weights[i];
+        }
+    }
+
+    let mut skinned = *vertex;
+    skinned.position = skin_matrix.transform_point3(vertex.position);
+    skinned.normal = skin_matrix.transform_vector3(vertex.normal);
+    skinned.tangent = skin_matrix.transform_vector3(vertex.tangent.xyz()).extend(vertex.tangent.w);
+    return skinned;
+}
+
+pub(crate) fn load_skins(document: &gltf::Document, mesh_data: &[gltf::buffer::Data]) -> Result<Vec<Skin>, AssetError> {
+    let mut skins = Vec::new();
+    for skin in document.skins() {
+        let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+
+        // Without inverse bind matrices, the joints are already in the bind pose
+        let inverse_bind_matrices = match skin.inverse_bind_matrices() {
+            Some(accessor) => {
+                let values = read_accessor_f32(&accessor, mesh_data)?;
+                let matrices: Vec<Mat4> = values.chunks_exact(16).map(Mat4::from_cols_slice).collect();
+                if matrices.len() < joints.len() {
+                    return Err(AssetError::MalformedData(format!(
+                        "skin {} has {} joints, but only {} inverse bind matrices",
+                        skin.index(),
+                        joints.len(),
+                        matrices.len()
+                    )));
+                }
+                matrices
+            }
+            None => vec![Mat4::IDENTITY; joints.len()],
+        };
+
+        skins.push(Skin {
+            name: String::from(skin.name().unwrap_or("untitled")),
+            joints,
+            inverse_bind_matrices,
+        });
+    }
+    return Ok(skins);
+}
+
+// Makes sure every vertex of a skinned mesh only refers to joints its skin actually has
+pub(crate) fn check_joint_indices(model: &Model) -> Result<(), AssetError> {
+    for node in &model.nodes {
+        let (Some(mesh), Some(skin)) = (node.mesh, node.skin) else {
+            continue;
+        };
+        let joint_count = model.skins[skin].joints.len();
+        for primitive in &model.meshes[mesh] {
+            for vertex in &primitive.mesh.verts {
+                for i in 0..4 {
+                    if vertex.weights[i] != 0.0 && vertex.joints[i] as usize >= joint_count {
+                        return Err(AssetError::MalformedData(format!(
+                            "node {} uses joint {}, but its skin only has {joint_count} joints",
+                            node.name, vertex.joints[i]
+                        )));
+                    }
+                }
+            }
+        }
+    }
+    return Ok(());
+}
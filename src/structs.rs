@@ -1,4 +1,4 @@
-use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use glam::{Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
 
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -9,6 +9,8 @@ pub struct Vertex {
     pub color: Vec4,
     pub uv0: Vec2,
     pub uv1: Vec2,
+    pub joints: UVec4,  // Indices into the joints of the node's skin, only used for skinned meshes
+    pub weights: Vec4,  // How much each joint influences the vertex
 }
 
 #[repr(C)]
@@ -16,17 +18,25 @@ pub struct ConstBuffer {
     pub model_matrix: Mat4,
     pub view_matrix: Mat4,
     pub proj_matrix: Mat4,
+    pub normal_matrix: Mat4, // Inverse transpose of the model matrix, see normal_matrix() in helpers.rs
+    pub joint_count: u32, // Size of the joint matrix buffer, 0 when the mesh is not skinned
+    pub light_count: u32, // Size of the light buffer, 0 draws the mesh unlit
+    pub _padding: [u32; 2],
+    pub camera_position: Vec4, // In world space, w is unused
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct FragIn {
     pub position: Vec4,
+    pub world_position: Vec3, // Used for lighting
     pub normal: Vec3,
-    pub tangent: Vec3,
-    pub color: Vec3,
-    pub uv: Vec2,
+    pub tangent: Vec4, // w is the handedness of the bitangent
+    pub color: Vec4,
+    pub uv0: Vec2,
+    pub uv1: Vec2,
 }
 
+#[derive(Debug, Copy, Clone)]
 pub struct Transform {
     pub translation: Vec3,
     pub rotation: Quat,
@@ -37,10 +47,24 @@ impl FragIn {
     pub fn lerp(&self, rhs: FragIn, t: f32) -> FragIn {
         FragIn {
             position: self.position.lerp(rhs.position, t),
+            world_position: self.world_position.lerp(rhs.world_position, t),
             normal: self.normal.lerp(rhs.normal, t),
             tangent: self.tangent.lerp(rhs.tangent, t),
             color: self.color.lerp(rhs.color, t),
-            uv: self.uv.lerp(rhs.uv, t),
+            uv0: self.uv0.lerp(rhs.uv0, t),
+            uv1: self.uv1.lerp(rhs.uv1, t),
+        }
+    }
+
+    pub fn barycentric(v0: &FragIn, v1: &FragIn, v2: &FragIn, weights: Vec3) -> FragIn {
+        FragIn {
+            position: v0.position * weights.x + v1.position * weights.y + v2.position * weights.z,
+            world_position: v0.world_position * weights.x + v1.world_position * weights.y + v2.world_position * weights.z,
+            normal: v0.normal * weights.x + v1.normal * weights.y + v2.normal * weights.z,
+            tangent: v0.tangent * weights.x + v1.tangent * weights.y + v2.tangent * weights.z,
+            color: v0.color * weights.x + v1.color * weights.y + v2.color * weights.z,
+            uv0: v0.uv0 * weights.x + v1.uv0 * weights.y + v2.uv0 * weights.z,
+            uv1: v0.uv1 * weights.x + v1.uv1 * weights.y + v2.uv1 * weights.z,
         }
     }
 }
@@ -57,12 +81,9 @@ impl Transform {
     pub fn forward(&self) -> Vec3 {
         self.rotation * -Vec3::Z
     }
+    // Inverse of the camera's rotation and translation, so cameras that roll (like the ones in glTF files) work too
     pub fn view_matrix(&self) -> Mat4 {
-        Mat4::look_at_rh(
-            self.translation,
-            self.translation + self.forward(),
-            glam::vec3(0.0, 1.0, 0.0),
-        )
+        Mat4::from_rotation_translation(self.rotation, self.translation).inverse()
     }
     pub fn trans_matrix(&self) -> Mat4 {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
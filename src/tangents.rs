@@ -0,0 +1,88 @@
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::mesh::{Mesh, PrimitiveType};
+
+impl Mesh {
+    // Generates tangents from the positions, normals and first UV set, the way MikkTSpace does, which is what the glTF spec asks for
+    // when a file has no tangents. Every triangle corner adds the direction its UVs increase in, projected onto the plane of the
+    // vertex normal and weighted by the angle of the corner. The w component is the handedness: the bitangent is
+    // cross(normal, tangent) * w, so mirrored UVs get -1. Vertices on a mirror seam, whose triangles disagree on the handedness,
+    // are split into one vertex per side, so the two sides don't cancel out. Only triangles have tangents, other meshes are left alone.
+    pub fn generate_tangents(&mut self) {
+        if self.primitive_type != PrimitiveType::Triangles {
+            return;
+        }
+
+        // Sums of the corner tangents of every vertex, one for each handedness: [right handed, left handed]
+        let indices = self.indices.to_u32();
+        let mut tangents = vec![[Vec3::ZERO; 2]; self.verts.len()];
+        let mut corner_sides: Vec<Option<usize>> = vec![None; indices.len()];
+        for (face, triangle) in indices.chunks_exact(3).enumerate() {
+            let corners = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let [p0, p1, p2] = corners.map(|index| self.verts[index].position);
+            let [uv0, uv1, uv2] = corners.map(|index| self.verts[index].uv0);
+            let (edge1, edge2) = (p1 - p0, p2 - p0);
+            let (delta_uv1, delta_uv2): (Vec2, Vec2) = (uv1 - uv0, uv2 - uv0);
+
+            // Triangles without any UV area don't say anything about the tangent, the other triangles of the vertex decide it
+            let uv_area = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+            if uv_area.abs() <= f32::EPSILON {
+                continue;
+            }
+            // glTF UVs start at the top of the image, but the green channel of a normal map points up, so the bitangent follows -v
+            let face_tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) / uv_area;
+            let face_bitangent = (edge1 * delta_uv2.x - edge2 * delta_uv1.x) / uv_area;
+            let face_normal = edge1.cross(edge2).normalize_or_zero();
+
+            for (corner, index) in corners.iter().enumerate() {
+                let normal = self.verts[*index].normal.try_normalize().unwrap_or(face_normal);
+                let project = |vector: Vec3| (vector - normal * normal.dot(vector)).normalize_or_zero();
+
+                // The angle between the two edges that meet at this corner, seen along the normal
+                let position = self.verts[*index].position;
+                let to_next = project(self.verts[corners[(corner + 1) % 3]].position - position);
+                let to_prev = project(self.verts[corners[(corner + 2) % 3]].position - position);
+                let angle = to_next.dot(to_prev).clamp(-1.0, 1.0).acos();
+
+                let side = if normal.cross(project(face_tangent)).dot(face_bitangent) < 0.0 { 1 } else { 0 };
+                tangents[*index][side] += project(face_tangent) * angle;
+                corner_sides[face * 3 + corner] = Some(side);
+            }
+        }
+
+        // Corners of triangles without UVs join whichever side the rest of the vertex is on
+        let mut vertex_sides: Vec<Option<usize>> = vec![None; self.verts.len()];
+        for (index, side) in indices.iter().zip(&corner_sides) {
+            vertex_sides[*index as usize] = vertex_sides[*index as usize].or(*side);
+        }
+        let corner_sides: Vec<usize> = indices
+            .iter()
+            .zip(&corner_sides)
+            .map(|(index, side)| side.or(vertex_sides[*index as usize]).unwrap_or(0))
+            .collect();
+
+        let tangent_space = |normal: Vec3, tangent: Vec3, side: usize| -> Vec4 {
+            let normal = normal.normalize_or_zero();
+            let mut tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+
+            // Vertices that only touch triangles without UVs still need a valid tangent space
+            if tangent == Vec3::ZERO {
+                tangent = if normal == Vec3::ZERO { Vec3::X } else { normal.any_orthonormal_vector() };
+            }
+            tangent.extend(if side == 1 { -1.0 } else { 1.0 })
+        };
+        let corner_tangents: Vec<Vec4> = indices
+            .iter()
+            .zip(&corner_sides)
+            .map(|(index, side)| tangent_space(self.verts[*index as usize].normal, tangents[*index as usize][*side], *side))
+            .collect();
+        self.split_corners(&indices, &corner_sides, |vertex, corner| vertex.tangent = corner_tangents[corner]);
+
+        // Vertices that aren't part of any triangle still get a tangent
+        for (vertex, side) in self.verts.iter_mut().zip(vertex_sides) {
+            if side.is_none() {
+                vertex.tangent = tangent_space(vertex.normal, Vec3::ZERO, 0);
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use crate::helpers::*;
+use crate::error::AssetError;
 use std::path::Path;
 
+#[derive(Clone)]
 pub struct Texture {
     pub gl_id: u32,
     pub width: usize,
@@ -9,27 +10,83 @@ pub struct Texture {
     pub data: Vec<u32>,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FilterMode {
     Point,
     Linear,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum WrapMode {
     Repeat,
     Mirror,
     Clamp,
 }
 
+// How a texture is filtered and wrapped, S is the horizontal axis and T the vertical one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Sampler {
     pub filter_mode_mag: FilterMode,
     pub filter_mode_min: FilterMode,
-    pub filter_mode_mipmap: FilterMode,
+    pub filter_mode_mipmap: FilterMode, // Only used when mipmap_enabled is set
     pub wrap_mode_s: WrapMode,
     pub wrap_mode_t: WrapMode,
     pub mipmap_enabled: bool,
 }
 
+impl Sampler {
+    // glTF leaves the filters of textures without a sampler up to the renderer, and they have always been drawn unfiltered.
+    // Wrapping defaults to repeat, like the spec says.
+    pub fn new() -> Self {
+        Sampler {
+            filter_mode_mag: FilterMode::Point,
+            filter_mode_min: FilterMode::Point,
+            filter_mode_mipmap: FilterMode::Point,
+            wrap_mode_s: WrapMode::Repeat,
+            wrap_mode_t: WrapMode::Repeat,
+            mipmap_enabled: false,
+        }
+    }
+
+    // Filters the sampler doesn't specify keep their default
+    pub fn from_gltf(sampler: &gltf::texture::Sampler) -> Self {
+        use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+        let mut result = Sampler::new();
+        if let Some(filter) = sampler.mag_filter() {
+            result.filter_mode_mag = match filter {
+                MagFilter::Nearest => FilterMode::Point,
+                MagFilter::Linear => FilterMode::Linear,
+            };
+        }
+        if let Some(filter) = sampler.min_filter() {
+            // The minification filter and the filter between mip levels are combined in one value
+            (result.filter_mode_min, result.filter_mode_mipmap, result.mipmap_enabled) = match filter {
+                MinFilter::Nearest => (FilterMode::Point, FilterMode::Point, false),
+                MinFilter::Linear => (FilterMode::Linear, FilterMode::Point, false),
+                MinFilter::NearestMipmapNearest => (FilterMode::Point, FilterMode::Point, true),
+                MinFilter::LinearMipmapNearest => (FilterMode::Linear, FilterMode::Point, true),
+                MinFilter::NearestMipmapLinear => (FilterMode::Point, FilterMode::Linear, true),
+                MinFilter::LinearMipmapLinear => (FilterMode::Linear, FilterMode::Linear, true),
+            };
+        }
+        let wrap_mode = |mode: WrappingMode| match mode {
+            WrappingMode::ClampToEdge => WrapMode::Clamp,
+            WrappingMode::MirroredRepeat => WrapMode::Mirror,
+            WrappingMode::Repeat => WrapMode::Repeat,
+        };
+        result.wrap_mode_s = wrap_mode(sampler.wrap_s());
+        result.wrap_mode_t = wrap_mode(sampler.wrap_t());
+        return result;
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone)]
 enum PixelComp {
     Skip,
@@ -40,57 +97,75 @@ enum PixelComp {
 }
 
 impl Texture {
-    pub fn load(path: &Path) -> Self {
+    // Whether the texture has no texels at all, because its width or height is 0
+    pub fn is_empty(&self) -> bool {
+        return self.width == 0 || self.height == 0;
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AssetError> {
         //Load image
         let loaded_image = stb_image::image::load(path);
 
-        //Map the image data to argb8 format
-        if let stb_image::image::LoadResult::ImageU8(image) = loaded_image {
-            if image.depth == 4 {
-                let data = (0..image.data.len() / 4)
-                    .map(|id| {
-                        color_rgba(
-                            image.data[id * 4 + 3],
-                            image.data[id * 4],
-                            image.data[id * 4 + 1],
-                            image.data[id * 4 + 2],
-                        )
-                    })
-                    .collect();
-                Self {
-                    gl_id: 0,
-                    width: image.width,
-                    height: image.height,
-                    depth: image.depth,
-                    data,
-                }
-            } else if image.depth == 3 {
-                let data = (0..image.data.len() / 3)
-                    .map(|id| {
-                        color_rgba(
-                            255,
-                            image.data[id * 3],
-                            image.data[id * 3 + 1],
-                            image.data[id * 3 + 2],
-                        )
-                    })
-                    .collect();
-                Self {
-                    gl_id: 0,
-                    width: image.width,
-                    height: image.height,
-                    depth: image.depth,
-                    data,
+        //Map the image data to RGBA8, with red in the lowest byte
+        let image = match loaded_image {
+            stb_image::image::LoadResult::ImageU8(image) => image,
+            stb_image::image::LoadResult::ImageF32(_) => {
+                return Err(AssetError::UnsupportedFormat(format!("\"{}\" is an HDR image", path.display())));
+            }
+            stb_image::image::LoadResult::Error(message) => {
+                // stb_image doesn't tell us why it failed, so check whether the file is readable at all
+                if let Err(source) = std::fs::metadata(path) {
+                    return Err(AssetError::Io { path: path.to_path_buf(), source });
                 }
-            } else {
-                panic!("Unsupported texture type");
+                return Err(AssetError::Parse { path: path.to_path_buf(), message });
             }
+        };
+        if image.depth == 4 {
+            let data = (0..image.data.len() / 4)
+                .map(|id| {
+                    u32::from_le_bytes([
+                        image.data[id * 4],
+                        image.data[id * 4 + 1],
+                        image.data[id * 4 + 2],
+                        image.data[id * 4 + 3],
+                    ])
+                })
+                .collect();
+            Ok(Self {
+                gl_id: 0,
+                width: image.width,
+                height: image.height,
+                depth: image.depth,
+                data,
+            })
+        } else if image.depth == 3 {
+            let data = (0..image.data.len() / 3)
+                .map(|id| {
+                    u32::from_le_bytes([
+                        image.data[id * 3],
+                        image.data[id * 3 + 1],
+                        image.data[id * 3 + 2],
+                        255,
+                    ])
+                })
+                .collect();
+            Ok(Self {
+                gl_id: 0,
+                width: image.width,
+                height: image.height,
+                depth: image.depth,
+                data,
+            })
         } else {
-            panic!("Unsupported texture type");
+            Err(AssetError::UnsupportedFormat(format!(
+                "\"{}\" has {} channels, only RGB and RGBA are supported",
+                path.display(),
+                image.depth
+            )))
         }
     }
 
-    pub fn load_texture_from_gltf_image(image: &gltf::image::Data) -> Texture {
+    pub fn load_texture_from_gltf_image(image: &gltf::image::Data) -> Result<Texture, AssetError> {
         // Get pixel swizzle pattern
         let swizzle_pattern = match image.format {
             gltf::image::Format::R8 => vec![PixelComp::Red],
@@ -127,9 +202,23 @@ impl Texture {
                 PixelComp::Skip,
                 PixelComp::Alpha,
             ],
-            _ => panic!("Texture format unsupported!"),
+            format => {
+                return Err(AssetError::UnsupportedFormat(format!("{format:?} textures")));
+            }
         };
-        Texture {
+        if image.width == 0 || image.height == 0 {
+            return Err(AssetError::MalformedData(format!("{}x{} image has no pixels", image.width, image.height)));
+        }
+        let pixel_count = image.width as usize * image.height as usize;
+        if image.pixels.len() < pixel_count * swizzle_pattern.len() {
+            return Err(AssetError::MalformedData(format!(
+                "{}x{} image only has {} bytes of pixel data",
+                image.width,
+                image.height,
+                image.pixels.len()
+            )));
+        }
+        Ok(Texture {
             gl_id: 0,
             width: image.width as usize,
             height: image.height as usize,
@@ -162,6 +251,6 @@ impl Texture {
                 }
                 data
             },
-        }
+        })
     }
 }
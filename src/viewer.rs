@@ -0,0 +1,142 @@
+use std::{path::Path, collections::HashMap, time::Instant};
+use glam::{Vec3, Quat, Vec2};
+use metal::objc::rc::autoreleasepool;
+use winit::{event::{Event, WindowEvent, VirtualKeyCode, DeviceEvent, MouseButton}, event_loop::ControlFlow};
+
+use rust_render_metal::{AnimationPlayer, Renderer, RenderBackend, ModelQueueEntry, Transform};
+
+// Credits to https://github.com/gfx-rs/metal-rs/blob/master/examples/window/main.rs for the base structure
+pub fn run() {
+    // Create a window
+    let event_loop = winit::event_loop::EventLoop::new();
+    let res = winit::dpi::LogicalSize::new(1280, 720);
+    let window = winit::window::WindowBuilder::new()
+        .with_inner_size(res)
+        .with_title("RustRenderMetal".to_string())
+        .build(&event_loop)
+        .unwrap();
+
+    // Initialize renderer, this compiles the Metal shaders
+    let mut renderer = match Renderer::new(&window) {
+        Ok(renderer) => renderer,
+        Err(err) => {println!("Error creating renderer: {err}"); return}
+    };
+    renderer.prepare_pipeline_state("hello_triangle_vertex", "hello_triangle_fragment");
+
+    // Models that fail to load are reported and left out of the scene
+    let load_model = |renderer: &mut Renderer, path: &str| match renderer.load_model(Path::new(path)) {
+        Ok(model) => Some(model),
+        Err(err) => {println!("Error loading model \"{path}\": {err}"); None}
+    };
+    let model_suzanne = load_model(&mut renderer, "./assets/suzanne.gltf");
+    let model_gun = load_model(&mut renderer, "./assets/sub_nivis_gun.gltf");
+
+    // Play the first animation of every model that has one
+    let mut animation_players: Vec<_> = [model_suzanne, model_gun].into_iter()
+        .flatten()
+        .filter(|model| !renderer.model(*model).unwrap().animations.is_empty())
+        .map(|model| (model, AnimationPlayer::new(0)))
+        .collect();
+
+    let mut camera = Transform {
+        translation: Vec3 {x: 0.0, y: 0.0, z: 0.5},
+        rotation: Quat::IDENTITY,
+        scale: Vec3 {x: 1.0, y: 1.0, z: 1.0},
+    };
+
+    // Main loop
+    let mut x = 0.0;
+    let mut time_curr = Instant::now();
+    let mut time_prev = Instant::now();
+    let mut key_held = HashMap::<VirtualKeyCode, bool>::new();
+    let mut mouse_held = HashMap::<MouseButton, bool>::new();
+    let camera_speed = 1.0;
+    let mut delta_mouse_pos = Some(Vec2{x:0.0, y: 0.0});
+    let mut camera_rotation = Vec3{x: 0.0, y: 0.0, z: 0.0};
+    let mouse_sensitivity = -0.01;
+    event_loop.run(move |event, _, control_flow| {
+        autoreleasepool(|| {
+            *control_flow = ControlFlow::Poll;
+
+            match event {
+                Event::WindowEvent{event, ..} => match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(size) => renderer.resize_framebuffer(size.width, size.height),
+                    WindowEvent::KeyboardInput { device_id: _, input, is_synthetic: _ } => {
+                        match input.state {
+                            winit::event::ElementState::Pressed => {
+                                if let Some(keycode) = input.virtual_keycode {
+                                    key_held.insert(keycode, true);
+                                }
+                            },
+                            winit::event::ElementState::Released => {
+                                if let Some(keycode) = input.virtual_keycode {
+                                    key_held.insert(keycode, false);
+                                }
+                            }
+                        };
+                    },
+                    WindowEvent::MouseInput { device_id: _, state, button, .. } => {
+                        match state {
+                            winit::event::ElementState::Pressed => mouse_held.insert(button, true),
+                            winit::event::ElementState::Released => mouse_held.insert(button, false)
+                        };
+                    },
+                    _ => (),
+                }
+                Event::DeviceEvent{ device_id: _, event: DeviceEvent::MouseMotion { delta } } => {
+                    delta_mouse_pos = Some(Vec2{x: delta.0 as f32, y: delta.1 as f32});
+                },
+                Event::MainEventsCleared => {
+                    window.request_redraw();
+                }
+                Event::RedrawRequested(_) => {
+                    time_prev = time_curr;
+                    time_curr = Instant::now();
+                    let delta_time = (time_curr - time_prev).as_secs_f32();
+                    x += delta_time * 2.0;
+                    if *key_held.entry(VirtualKeyCode::D).or_insert(false) {camera.translation += delta_time * camera_speed * camera.right();}
+                    if *key_held.entry(VirtualKeyCode::A).or_insert(false) {camera.translation -= delta_time * camera_speed * camera.right();}
+                    if *key_held.entry(VirtualKeyCode::W).or_insert(false) {camera.translation += delta_time * camera_speed * camera.forward();}
+                    if *key_held.entry(VirtualKeyCode::S).or_insert(false) {camera.translation -= delta_time * camera_speed * camera.forward();}
+                    if *key_held.entry(VirtualKeyCode::Space).or_insert(false) {camera.translation += delta_time * camera_speed * camera.up();}
+                    if *key_held.entry(VirtualKeyCode::LShift).or_insert(false) {camera.translation -= delta_time * camera_speed * camera.up();}
+                    if let Some(delta_mouse) = delta_mouse_pos {
+                        if *mouse_held.entry(MouseButton::Right).or_insert(false) {
+                            camera_rotation.x += delta_mouse.x * mouse_sensitivity;
+                            camera_rotation.y += delta_mouse.y * mouse_sensitivity;
+                            delta_mouse_pos = None;
+                        }
+                    }
+                    camera.rotation = Quat::from_euler(glam::EulerRot::YXZ, camera_rotation.x, camera_rotation.y, camera_rotation.z);
+                    for (model, player) in &mut animation_players {
+                        player.update(delta_time, renderer.model_mut(*model).unwrap());
+                    }
+                    renderer.update_camera(&camera);
+                    renderer.begin_frame();
+                    if let Some(model_gun) = model_gun {
+                        renderer.draw_model(ModelQueueEntry{
+                            model: model_gun,
+                            transform: Transform { 
+                                translation: Vec3{x: 0.0, y: 0.0, z: 0.0}, 
+                                rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, x, 0.0), 
+                                scale: Vec3{x: 1.0, y: 1.0, z: 1.0} }
+                        });
+                    }
+                    if let Some(model_suzanne) = model_suzanne {
+                        renderer.draw_model(ModelQueueEntry{
+                            model: model_suzanne,
+                            transform: Transform { 
+                                translation: Vec3{x: 0.5, y: 0.0, z: 0.0}, 
+                                rotation: Quat::from_euler(glam::EulerRot::XYZ, 0.0, x, 0.0), 
+                                scale: Vec3{x: 0.1, y: 0.1, z: 0.1} }
+                        });
+                    }
+                    renderer.end_frame();
+                    window.request_redraw();
+                }
+                _ => {}
+            }
+        });
+    });
+}
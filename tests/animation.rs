@@ -0,0 +1,162 @@
+use std::f32::consts::FRAC_PI_4;
+use std::path::Path;
+
+use glam::{Quat, Vec3};
+use rust_render_metal::animation::Interpolation;
+use rust_render_metal::{AnimationPlayer, AssetError, LoadOptions, Model, SoftwareRenderer};
+
+fn load_animations() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/animations.gltf"), &mut renderer).unwrap()
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, b) in actual.iter().zip(expected) {
+        assert!((a - b).abs() < 1e-5, "{actual:?} != {expected:?}");
+    }
+}
+
+#[test]
+fn clips_and_channels_are_loaded() {
+    let model = load_animations();
+    let names: Vec<&str> = model.animations.iter().map(|animation| animation.name.as_str()).collect();
+    assert_eq!(names, ["step", "linear", "cubic", "weights"]);
+    assert_eq!(model.animations[0].duration, 2.0);
+    assert_eq!(model.animations[1].duration, 2.0);
+    assert_eq!(model.animations[1].channels.len(), 2);
+    assert_eq!(model.animations[2].channels[0].interpolation, Interpolation::CubicSpline);
+    assert_eq!(model.animations[3].channels[0].components, 2);
+}
+
+#[test]
+fn step_holds_the_previous_keyframe() {
+    let model = load_animations();
+    let channel = &model.animations[model.find_animation("step").unwrap()].channels[0];
+    assert_close(&channel.sample(0.0), &[0.0, 0.0, 0.0]);
+    assert_close(&channel.sample(0.99), &[0.0, 0.0, 0.0]);
+    assert_close(&channel.sample(1.0), &[1.0, 0.0, 0.0]);
+    assert_close(&channel.sample(1.5), &[1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn linear_lerps_and_slerps() {
+    let model = load_animations();
+    let animation = &model.animations[model.find_animation("linear").unwrap()];
+    // The rotation goes from identity to 90 degrees around Y, so halfway is 45 degrees
+    let rotation = animation.channels[0].sample(0.5);
+    assert_close(&rotation, &Quat::from_rotation_y(FRAC_PI_4).to_array());
+    assert_close(&animation.channels[1].sample(0.5), &[0.5, 1.0, 0.0]);
+}
+
+#[test]
+fn cubic_splines_use_the_tangents() {
+    let model = load_animations();
+    let channel = &model.animations[model.find_animation("cubic").unwrap()].channels[0];
+    // From 0 to 1 with an out-tangent of 1 on the first keyframe, at t = 0.25:
+    // (-2t^3 + 3t^2) * 1 + (t^3 - 2t^2 + t) * 1 = 0.15625 + 0.140625
+    assert_close(&channel.sample(0.25), &[0.296875, 0.0, 0.0]);
+    assert_close(&channel.sample(1.0), &[1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn times_outside_the_clip_hold_the_ends() {
+    let model = load_animations();
+    let channel = &model.animations[model.find_animation("linear").unwrap()].channels[1];
+    assert_close(&channel.sample(-1.0), &[0.0, 0.0, 0.0]);
+    assert_close(&channel.sample(10.0), &[2.0, 4.0, 0.0]);
+}
+
+#[test]
+fn players_drive_node_transforms() {
+    let mut model = load_animations();
+    let mover = model.find_node("mover").unwrap();
+    let mut player = AnimationPlayer::new(model.find_animation("linear").unwrap());
+
+    player.update(1.0, &mut model);
+    assert!(model.nodes[mover].transform.translation.abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1e-5));
+    assert!(model.nodes[mover].transform.rotation.abs_diff_eq(Quat::from_rotation_y(FRAC_PI_4 * 2.0), 1e-5));
+
+    // The model's world matrices follow once they're updated
+    model.update_world_matrices();
+    assert!(model.nodes[mover].world_matrix.w_axis.truncate().abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1e-5));
+}
+
+#[test]
+fn players_loop_clamp_pause_and_change_speed() {
+    let mut model = load_animations();
+    let mut player = AnimationPlayer::new(model.find_animation("linear").unwrap());
+
+    // Looping wraps around the 2 second clip
+    player.update(2.5, &mut model);
+    assert!((player.time - 0.5).abs() < 1e-5);
+
+    // Without looping it holds the last frame
+    player.looping = false;
+    player.update(5.0, &mut model);
+    assert_eq!(player.time, 2.0);
+
+    // Paused players don't advance, but seeking still works
+    player.paused = true;
+    player.seek(0.5);
+    player.update(1.0, &mut model);
+    assert_eq!(player.time, 0.5);
+
+    // Negative speeds play the clip backwards
+    player.paused = false;
+    player.speed = -0.5;
+    player.update(0.5, &mut model);
+    assert_eq!(player.time, 0.25);
+    let mover = model.find_node("mover").unwrap();
+    assert!(model.nodes[mover].transform.translation.abs_diff_eq(Vec3::new(0.25, 0.5, 0.0), 1e-5));
+}
+
+#[test]
+fn weight_channels_set_morph_weights() {
+    let mut model = load_animations();
+    let mover = model.find_node("mover").unwrap();
+    assert_eq!(model.nodes[mover].weights, [0.25, 0.75]);
+
+    let mut player = AnimationPlayer::new(model.find_animation("weights").unwrap());
+    player.looping = false;
+    player.update(0.5, &mut model);
+    assert_close(&model.nodes[mover].weights, &[0.5, 0.25]);
+}
+
+// A translation channel with 3 keyframes at the given times, all moving the node to the origin
+fn load_keyframe_times(times: [f32; 3]) -> Result<Model, AssetError> {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{}],
+        "animations": [{
+            "channels": [{ "sampler": 0, "target": { "node": 0, "path": "translation" } }],
+            "samplers": [{ "input": 0, "output": 1 }]
+        }],
+        "buffers": [{ "byteLength": 48, "uri": "keyframes.bin" }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 12 }, { "buffer": 0, "byteOffset": 12, "byteLength": 36 }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "SCALAR" },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }
+        ]
+    }"#;
+    let mut resolver = |_: &str| {
+        let mut bytes: Vec<u8> = times.iter().flat_map(|time| time.to_le_bytes()).collect();
+        bytes.resize(48, 0);
+        Ok(bytes)
+    };
+    Model::load_gltf_from_slice(json.as_bytes(), &mut resolver, &mut SoftwareRenderer::new(4, 4), &LoadOptions::default())
+}
+
+#[test]
+fn keyframe_times_have_to_increase() {
+    assert!(load_keyframe_times([0.0, 1.0, 2.0]).is_ok());
+    assert!(matches!(load_keyframe_times([0.0, 2.0, 1.0]), Err(AssetError::MalformedData(_))));
+    assert!(matches!(load_keyframe_times([0.0, 1.0, 1.0]), Err(AssetError::MalformedData(_))));
+}
+
+#[test]
+fn keyframe_times_have_to_be_finite() {
+    assert!(matches!(load_keyframe_times([0.0, f32::NAN, 2.0]), Err(AssetError::MalformedData(_))));
+    assert!(matches!(load_keyframe_times([0.0, 1.0, f32::INFINITY]), Err(AssetError::MalformedData(_))));
+}
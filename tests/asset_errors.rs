@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use rust_render_metal::{AssetError, LoadOptions, Model, RenderBackend, SoftwareRenderer, Texture};
+
+#[test]
+fn missing_files_are_io_errors() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let result = renderer.load_model(Path::new("./assets/does_not_exist.gltf"));
+    assert!(matches!(result, Err(AssetError::Io { .. })));
+    assert!(matches!(Texture::load(Path::new("./assets/does_not_exist.png")), Err(AssetError::Io { .. })));
+}
+
+#[test]
+fn invalid_files_are_parse_errors() {
+    let path = std::env::temp_dir().join("rust_render_metal_invalid.gltf");
+    std::fs::write(&path, "{ this is not json").unwrap();
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let result = renderer.load_model(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(AssetError::Parse { .. })));
+}
+
+// A single point, with its position read through the given accessor count from a 12 byte buffer
+fn point_json(count: u32, buffer_uri: &str) -> String {
+    format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "mode": 0 }}] }}],
+            "buffers": [{{ "byteLength": 12, "uri": "{buffer_uri}" }}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 12 }}],
+            "accessors": [{{ "bufferView": 0, "componentType": 5126, "count": {count}, "type": "VEC3", "min": [0, 0, 0], "max": [0, 0, 0] }}]
+        }}"#
+    )
+}
+
+fn load_json(json: &str) -> Result<Model, AssetError> {
+    let mut resolver = |_: &str| Ok(vec![0; 12]);
+    Model::load_gltf_from_slice(json.as_bytes(), &mut resolver, &mut SoftwareRenderer::new(4, 4), &LoadOptions::default())
+}
+
+#[test]
+fn accessors_past_the_end_of_their_buffer_view_are_malformed() {
+    assert!(load_json(&point_json(1, "point.bin")).is_ok());
+    assert!(matches!(load_json(&point_json(2, "point.bin")), Err(AssetError::MalformedData(_))));
+}
+
+#[test]
+fn truncated_images_are_malformed() {
+    let image = gltf::image::Data {
+        pixels: vec![255; 4 * 3],
+        format: gltf::image::Format::R8G8B8A8,
+        width: 2,
+        height: 2,
+    };
+    assert!(matches!(Texture::load_texture_from_gltf_image(&image), Err(AssetError::MalformedData(_))));
+}
+
+#[test]
+fn empty_images_are_malformed() {
+    for (width, height) in [(0, 4), (4, 0)] {
+        let image = gltf::image::Data {
+            pixels: vec![255; 4 * 4],
+            format: gltf::image::Format::R8G8B8A8,
+            width,
+            height,
+        };
+        assert!(matches!(Texture::load_texture_from_gltf_image(&image), Err(AssetError::MalformedData(_))));
+    }
+}
+
+#[test]
+fn float_images_are_unsupported() {
+    let image = gltf::image::Data {
+        pixels: vec![0; 12],
+        format: gltf::image::Format::R32G32B32FLOAT,
+        width: 1,
+        height: 1,
+    };
+    assert!(matches!(Texture::load_texture_from_gltf_image(&image), Err(AssetError::UnsupportedFormat(_))));
+}
+
+#[test]
+fn two_channel_images_are_unsupported() {
+    let path = std::env::temp_dir().join("rust_render_metal_grey_alpha.png");
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, 1, 1);
+    encoder.set_color(png::ColorType::GrayscaleAlpha);
+    encoder.write_header().unwrap().write_image_data(&[128, 255]).unwrap();
+    std::fs::write(&path, png).unwrap();
+    let result = Texture::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(AssetError::UnsupportedFormat(_))));
+}
+
+#[test]
+fn unknown_uri_schemes_are_unsupported() {
+    let result = load_json(&point_json(1, "https://example.com/point.bin"));
+    assert!(matches!(result, Err(AssetError::UnsupportedFormat(_))));
+}
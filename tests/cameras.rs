@@ -0,0 +1,142 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+use rust_render_metal::{Model, ModelQueueEntry, Projection, RenderBackend, SoftwareRenderer, Transform};
+
+const WHITE: u32 = 0xFFFFFFFF;
+
+fn identity() -> Transform {
+    Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    }
+}
+
+fn load_cameras() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/cameras.gltf"), &mut renderer).unwrap()
+}
+
+#[test]
+fn cameras_are_loaded() {
+    let model = load_cameras();
+    assert_eq!(model.cameras.len(), 3);
+    assert_eq!(
+        model.cameras[0].projection,
+        Projection::Perspective {
+            yfov: PI / 4.0,
+            aspect_ratio: Some(1.0),
+            znear: 0.1,
+            zfar: Some(100.0),
+        }
+    );
+    assert_eq!(
+        model.cameras[1].projection,
+        Projection::Orthographic {
+            xmag: 2.5,
+            ymag: 2.5,
+            znear: 0.01,
+            zfar: 20.0,
+        }
+    );
+    // Without a far plane the projection is infinite
+    assert_eq!(
+        model.cameras[2].projection,
+        Projection::Perspective {
+            yfov: 1.0,
+            aspect_ratio: None,
+            znear: 0.5,
+            zfar: None,
+        }
+    );
+    assert_eq!(model.find_camera("orthographic"), Some(1));
+    assert_eq!(model.nodes[model.find_node("side").unwrap()].camera, Some(1));
+}
+
+#[test]
+fn projections_map_depth_to_zero_one() {
+    let perspective = Projection::Perspective {
+        yfov: PI / 2.0,
+        aspect_ratio: None,
+        znear: 1.0,
+        zfar: Some(10.0),
+    };
+    let near = perspective.matrix(2.0) * Vec4::new(0.0, 1.0, -1.0, 1.0);
+    let far = perspective.matrix(2.0) * Vec4::new(2.0, 0.0, -10.0, 1.0);
+    assert!((near.z / near.w).abs() < 1e-5);
+    assert!((near.y / near.w - 1.0).abs() < 1e-5);
+    assert!((far.z / far.w - 1.0).abs() < 1e-5);
+    // The framebuffer's aspect ratio is used when the camera doesn't have one
+    assert!((far.x / far.w - 0.1).abs() < 1e-5);
+
+    let orthographic = Projection::Orthographic {
+        xmag: 2.0,
+        ymag: 1.0,
+        znear: 1.0,
+        zfar: 3.0,
+    };
+    let corner = orthographic.matrix(16.0 / 9.0).project_point3(Vec3::new(2.0, -1.0, -3.0));
+    assert!((corner - Vec3::new(1.0, -1.0, 1.0)).abs().max_element() < 1e-5);
+}
+
+#[test]
+fn camera_transforms_follow_their_nodes_without_scale() {
+    let model = load_cameras();
+    let front = model.camera_transform(0).unwrap();
+    assert!((front.translation - Vec3::new(0.0, 0.0, 5.0)).length() < 1e-5);
+
+    // The rig turns the camera to look down -X, and its scale moves the camera out to x = 11
+    let side = model.camera_transform(1).unwrap();
+    assert!((side.translation - Vec3::new(11.0, 0.0, 0.0)).length() < 1e-4);
+    assert!((side.forward() - Vec3::NEG_X).length() < 1e-5);
+    assert_eq!(side.scale, Vec3::ONE);
+
+    // No node uses the last camera
+    assert!(model.camera_transform(2).is_none());
+}
+
+#[test]
+fn view_matrix_is_the_inverse_of_the_camera_transform() {
+    let camera = Transform {
+        translation: Vec3::new(1.0, 2.0, 3.0),
+        rotation: Quat::from_euler(glam::EulerRot::YXZ, 0.3, -0.2, 0.5),
+        scale: Vec3::ONE,
+    };
+    let camera_space = camera.view_matrix() * Mat4::from_rotation_translation(camera.rotation, camera.translation);
+    assert!(camera_space.abs_diff_eq(Mat4::IDENTITY, 1e-5));
+}
+
+fn render_from(camera: usize) -> Option<Vec<u32>> {
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/cameras.gltf")).unwrap();
+    if !renderer.use_gltf_camera(model, camera, &identity()).unwrap() {
+        return None;
+    }
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity() });
+    renderer.end_frame();
+    Some(renderer.framebuffer().to_vec())
+}
+
+#[test]
+fn render_from_perspective_camera() {
+    // The point at (1, 1, 0.5) is up and to the right of the camera at (0, 0, 5)
+    let framebuffer = render_from(0).unwrap();
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 1);
+    assert_eq!(framebuffer[12 + 3 * 16], WHITE);
+}
+
+#[test]
+fn render_from_orthographic_camera() {
+    // Looking down -X, +Z is to the left. The point is 0.5 / 2.5 of the half width left, and 1 / 2.5 of the half height up.
+    let framebuffer = render_from(1).unwrap();
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 1);
+    assert_eq!(framebuffer[6 + 4 * 16], WHITE);
+}
+
+#[test]
+fn cameras_outside_the_scene_are_ignored() {
+    assert!(render_from(2).is_none());
+}
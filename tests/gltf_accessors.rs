@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use glam::{Vec2, Vec3};
+use rust_render_metal::{Model, SoftwareRenderer};
+
+fn load(path: &str) -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new(path), &mut renderer).unwrap()
+}
+
+#[test]
+fn interleaved_attributes_respect_offset_and_stride() {
+    let model = load("./tests/assets/interleaved.gltf");
+    let mesh = &model.meshes[0][0].mesh;
+    let positions: Vec<Vec3> = mesh.verts.iter().map(|vertex| vertex.position).collect();
+    assert_eq!(positions, [Vec3::ZERO, Vec3::X, Vec3::Y]);
+    // The index accessor starts 2 bytes into its buffer view
+    assert_eq!(mesh.indices.to_u32(), [0, 1, 2]);
+}
+
+#[test]
+fn normalized_integers_are_mapped_to_unit_range() {
+    let model = load("./tests/assets/interleaved.gltf");
+    let mesh = &model.meshes[0][0].mesh;
+    // Texture coordinates are stored as normalized u16
+    assert_eq!(mesh.verts[0].uv0, Vec2::ZERO);
+    assert_eq!(mesh.verts[1].uv0, Vec2::X);
+    assert!((mesh.verts[2].uv0.y - 32768.0 / 65535.0).abs() < 1e-6);
+    // Colors are stored as normalized u8
+    assert_eq!(mesh.verts[0].color.x, 1.0);
+    assert_eq!(mesh.verts[0].color.y, 0.0);
+    assert!((mesh.verts[1].color.w - 128.0 / 255.0).abs() < 1e-6);
+    assert_eq!(mesh.verts[2].color.w, 0.0);
+}
+
+#[test]
+fn sparse_values_replace_base_values() {
+    let model = load("./tests/assets/sparse.gltf");
+    let mesh = &model.meshes[0][0].mesh;
+    let positions: Vec<Vec3> = mesh.verts.iter().map(|vertex| vertex.position).collect();
+    assert_eq!(positions, [Vec3::ZERO, Vec3::X, Vec3::new(0.0, 2.0, 0.0)]);
+}
+
+#[test]
+fn sparse_accessors_without_buffer_view_start_as_zeros() {
+    let model = load("./tests/assets/sparse.gltf");
+    let mesh = &model.meshes[0][0].mesh;
+    let normals: Vec<Vec3> = mesh.verts.iter().map(|vertex| vertex.normal).collect();
+    assert_eq!(normals, [Vec3::Z, Vec3::ZERO, Vec3::Y]);
+}
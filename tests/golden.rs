@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+use rust_render_metal::golden::{self, GoldenOptions, GoldenTolerance};
+
+// Regenerate the reference images with `cargo run -- --golden tests/golden --update`
+#[test]
+fn golden_images_match() {
+    let options = GoldenOptions {
+        reference_dir: PathBuf::from("tests/golden"),
+        output_dir: PathBuf::from("target/golden"),
+        update: false,
+        tolerance: GoldenTolerance::default(),
+    };
+    if let Err(err) = golden::run(&options) {
+        panic!("{err}");
+    }
+}
@@ -0,0 +1,123 @@
+use std::mem::{offset_of, size_of};
+
+use rust_render_metal::light::LightData;
+use rust_render_metal::material::MaterialData;
+use rust_render_metal::structs::{ConstBuffer, Vertex};
+
+const SHADER: &str = include_str!("../metal/shaders/hello_triangle.metal");
+
+// The size and alignment of a Metal shading language type
+fn msl_type(name: &str) -> (usize, usize) {
+    match name {
+        "float" | "uint" => (4, 4),
+        "float2" | "uint2" => (8, 8),
+        "packed_float3" => (12, 4),
+        "float3" | "float4" | "uint4" => (16, 16),
+        "float4x4" => (64, 16),
+        _ => panic!("unknown shader type {name}"),
+    }
+}
+
+// The fields of a struct in the shader, with the offsets Metal gives them, and the size of the whole struct
+fn shader_layout(struct_name: &str) -> (Vec<(String, usize)>, usize) {
+    let start = SHADER.find(&format!("struct {struct_name} {{")).unwrap_or_else(|| panic!("{struct_name} is not in the shader"));
+    let body = &SHADER[start..];
+    let body = &body[body.find('{').unwrap() + 1..body.find("};").unwrap()];
+
+    let (mut fields, mut offset, mut struct_align) = (Vec::new(), 0usize, 1);
+    for line in body.lines() {
+        let declaration = line.split("//").next().unwrap().trim();
+        let Some(declaration) = declaration.strip_suffix(';') else {
+            continue;
+        };
+        let (type_name, field) = declaration.split_once(' ').unwrap();
+        let (size, align) = msl_type(type_name);
+        offset = offset.next_multiple_of(align);
+        fields.push((field.trim().to_string(), offset));
+        offset += size;
+        struct_align = struct_align.max(align);
+    }
+    (fields, offset.next_multiple_of(struct_align))
+}
+
+fn assert_layout(struct_name: &str, rust_size: usize, rust_offsets: &[(&str, usize)]) {
+    let (fields, size) = shader_layout(struct_name);
+    let fields: Vec<(&str, usize)> = fields.iter().map(|(name, offset)| (name.as_str(), *offset)).collect();
+    assert_eq!(fields, rust_offsets, "{struct_name} doesn't match the Rust struct");
+    assert_eq!(size, rust_size, "{struct_name} has a different size than the Rust struct");
+}
+
+#[test]
+fn vertices_match_the_shader() {
+    assert_eq!(size_of::<Vertex>(), 112);
+    assert_layout(
+        "vertex_t",
+        size_of::<Vertex>(),
+        &[
+            ("position", offset_of!(Vertex, position)),
+            ("normal", offset_of!(Vertex, normal)),
+            ("tangent", offset_of!(Vertex, tangent)),
+            ("color", offset_of!(Vertex, color)),
+            ("uv0", offset_of!(Vertex, uv0)),
+            ("uv1", offset_of!(Vertex, uv1)),
+            ("joints", offset_of!(Vertex, joints)),
+            ("weights", offset_of!(Vertex, weights)),
+        ],
+    );
+}
+
+#[test]
+fn lights_match_the_shader() {
+    // The padding at the end is implicit in the shader
+    assert_layout(
+        "light_t",
+        size_of::<LightData>(),
+        &[
+            ("position", offset_of!(LightData, position)),
+            ("direction", offset_of!(LightData, direction)),
+            ("color", offset_of!(LightData, color)),
+            ("kind", offset_of!(LightData, kind)),
+            ("inner_cone_cos", offset_of!(LightData, inner_cone_cos)),
+            ("outer_cone_cos", offset_of!(LightData, outer_cone_cos)),
+        ],
+    );
+}
+
+#[test]
+fn constant_buffers_match_the_shader() {
+    assert_layout(
+        "const_buffer_t",
+        size_of::<ConstBuffer>(),
+        &[
+            ("model_matrix", offset_of!(ConstBuffer, model_matrix)),
+            ("view_matrix", offset_of!(ConstBuffer, view_matrix)),
+            ("proj_matrix", offset_of!(ConstBuffer, proj_matrix)),
+            ("normal_matrix", offset_of!(ConstBuffer, normal_matrix)),
+            ("joint_count", offset_of!(ConstBuffer, joint_count)),
+            ("light_count", offset_of!(ConstBuffer, light_count)),
+            ("_padding", offset_of!(ConstBuffer, _padding)),
+            ("camera_position", offset_of!(ConstBuffer, camera_position)),
+        ],
+    );
+}
+
+#[test]
+fn materials_match_the_shader() {
+    assert_layout(
+        "material_t",
+        size_of::<MaterialData>(),
+        &[
+            ("base_color", offset_of!(MaterialData, base_color)),
+            ("emissive", offset_of!(MaterialData, emissive)),
+            ("metallic", offset_of!(MaterialData, metallic)),
+            ("roughness", offset_of!(MaterialData, roughness)),
+            ("normal_scale", offset_of!(MaterialData, normal_scale)),
+            ("occlusion_strength", offset_of!(MaterialData, occlusion_strength)),
+            ("tex_coords", offset_of!(MaterialData, tex_coords)),
+            ("tex_coord_emm", offset_of!(MaterialData, tex_coord_emm)),
+            ("alpha_mode", offset_of!(MaterialData, alpha_mode)),
+            ("alpha_cutoff", offset_of!(MaterialData, alpha_cutoff)),
+            ("double_sided", offset_of!(MaterialData, double_sided)),
+        ],
+    );
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use rust_render_metal::handle::ResourcePool;
+use rust_render_metal::{HandleError, MaterialTexture, Model, RenderBackend, SoftwareRenderer, Texture, TextureHandle};
+
+#[test]
+fn removed_handles_are_stale() {
+    let mut pool = ResourcePool::<u32>::new();
+    let first = pool.insert(1);
+    assert_eq!(pool.remove(first), Ok(1));
+
+    // The slot gets reused, but the old handle must not see the new value
+    let second = pool.insert(2);
+    assert_eq!(first.index(), second.index());
+    assert_eq!(pool.get(first), Err(HandleError::Stale));
+    assert_eq!(pool.get(second), Ok(&2));
+    assert_eq!(pool.remove(first), Err(HandleError::Stale));
+}
+
+#[test]
+fn handles_from_other_renderers_are_foreign() {
+    let mut renderer_a = SoftwareRenderer::new(4, 4);
+    let mut renderer_b = SoftwareRenderer::new(4, 4);
+    let model = renderer_a.load_model(Path::new("./assets/suzanne.gltf")).unwrap();
+    assert_eq!(renderer_b.unload_model(model), Err(HandleError::Foreign));
+    assert_eq!(renderer_a.unload_model(model), Ok(()));
+    assert_eq!(renderer_a.unload_model(model), Err(HandleError::Stale));
+
+    let texture = renderer_a.upload_texture(&mut Texture { gl_id: 0, width: 1, height: 1, depth: 4, data: vec![0] });
+    let mut pool = ResourcePool::<Texture>::new();
+    assert!(matches!(pool.get_mut(texture), Err(HandleError::Foreign)));
+}
+
+#[test]
+fn unloading_frees_everything_even_when_a_handle_is_bad() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let mut other = SoftwareRenderer::new(4, 4);
+    let mut model = Model::load_gltf(Path::new("./tests/assets/pbr_material.gltf"), &mut renderer).unwrap();
+    let textures: Vec<TextureHandle> = model.textures().into_iter().collect();
+    // The first slot points into another renderer, so freeing it fails
+    model.materials[1].tex_alb = Some(MaterialTexture {
+        texture: other.upload_texture(&mut Texture { gl_id: 0, width: 1, height: 1, depth: 4, data: vec![0] }),
+        ..model.materials[0].tex_alb.unwrap()
+    });
+    let handle = renderer.store_model(model);
+
+    assert_eq!(renderer.unload_model(handle), Err(HandleError::Foreign));
+    // The model and its own textures are gone regardless
+    assert!(renderer.model(handle).is_err());
+    for texture in textures {
+        assert_eq!(renderer.unload_texture(texture), Err(HandleError::Stale));
+    }
+}
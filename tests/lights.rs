@@ -0,0 +1,162 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use glam::{Mat4, Quat, Vec3};
+use rust_render_metal::pbr::{linear_to_srgb, shade_surface, Surface};
+use rust_render_metal::{Light, LightKind, Model, ModelQueueEntry, PlacedLight, RenderBackend, SoftwareRenderer, Transform};
+
+fn load_lights() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/lights.gltf"), &mut renderer).unwrap()
+}
+
+fn light(kind: LightKind, intensity: f32, range: Option<f32>) -> Light {
+    Light {
+        name: "test".to_string(),
+        kind,
+        color: Vec3::ONE,
+        intensity,
+        range,
+    }
+}
+
+fn placed(light: Light, position: Vec3, direction: Vec3) -> PlacedLight {
+    PlacedLight { light, position, direction }
+}
+
+#[test]
+fn lights_are_loaded() {
+    let model = load_lights();
+    assert_eq!(model.lights.len(), 4);
+
+    let sun = &model.lights[model.find_light("sun").unwrap()];
+    assert_eq!(sun.kind, LightKind::Directional);
+    assert_eq!(sun.color, Vec3::ONE);
+    assert_eq!(sun.range, None);
+
+    let lamp = &model.lights[model.find_light("lamp").unwrap()];
+    assert_eq!(lamp.kind, LightKind::Point);
+    assert_eq!(lamp.color, Vec3::new(1.0, 0.5, 0.25));
+    assert_eq!(lamp.intensity, 4.0);
+    assert_eq!(lamp.range, Some(10.0));
+
+    let spot = &model.lights[model.find_light("spot").unwrap()];
+    assert_eq!(spot.kind, LightKind::Spot { inner_cone_angle: 0.2, outer_cone_angle: 0.5 });
+
+    // The spec's defaults for spot lights
+    let default_spot = &model.lights[model.find_light("default_spot").unwrap()];
+    assert_eq!(default_spot.kind, LightKind::Spot { inner_cone_angle: 0.0, outer_cone_angle: PI / 4.0 });
+    assert_eq!(default_spot.intensity, 1.0);
+
+    assert_eq!(model.nodes[model.find_node("lamp").unwrap()].light, model.find_light("lamp"));
+    assert_eq!(model.nodes[model.find_node("surface").unwrap()].light, None);
+}
+
+#[test]
+fn lights_are_placed_by_their_nodes() {
+    let model = load_lights();
+    let lights = model.placed_lights(Mat4::from_translation(Vec3::new(0.0, 0.0, -1.0)));
+    // The default spot light isn't used by any node
+    assert_eq!(lights.len(), 3);
+
+    let find = |name: &str| lights.iter().find(|light| light.light.name == name).unwrap();
+    assert!((find("sun").direction - Vec3::NEG_Z).length() < 1e-5);
+    assert!((find("lamp").position - Vec3::new(1.0, 2.0, -1.0)).length() < 1e-5);
+    assert!((find("spot").position - Vec3::new(0.0, 5.0, -1.0)).length() < 1e-5);
+    assert!((find("spot").direction - Vec3::NEG_Y).length() < 1e-5);
+}
+
+#[test]
+fn point_lights_fall_off_with_the_square_of_the_distance() {
+    let lamp = placed(light(LightKind::Point, 8.0, None), Vec3::ZERO, Vec3::NEG_Z);
+    let (near, to_light) = lamp.incoming_light(Vec3::new(0.0, 0.0, -1.0));
+    let (far, _) = lamp.incoming_light(Vec3::new(0.0, 0.0, -2.0));
+    assert!((near - Vec3::splat(8.0)).length() < 1e-4);
+    assert!((far - Vec3::splat(2.0)).length() < 1e-4);
+    assert!((to_light - Vec3::Z).length() < 1e-5);
+}
+
+#[test]
+fn range_cuts_lights_off_smoothly() {
+    let lamp = placed(light(LightKind::Point, 1.0, Some(4.0)), Vec3::ZERO, Vec3::NEG_Z);
+    let mut previous = f32::INFINITY;
+    for distance in [0.5, 1.0, 2.0, 3.0, 3.9] {
+        let (radiance, _) = lamp.incoming_light(Vec3::new(distance, 0.0, 0.0));
+        assert!(radiance.x > 0.0 && radiance.x < previous, "distance {distance}");
+        assert!(radiance.x <= 1.0 / (distance * distance));
+        previous = radiance.x;
+    }
+    assert_eq!(lamp.incoming_light(Vec3::new(4.0, 0.0, 0.0)).0, Vec3::ZERO);
+    assert_eq!(lamp.incoming_light(Vec3::new(0.0, 9.0, 0.0)).0, Vec3::ZERO);
+}
+
+#[test]
+fn spot_lights_fade_between_their_cones() {
+    let kind = LightKind::Spot { inner_cone_angle: 0.2, outer_cone_angle: 0.5 };
+    let spot = placed(light(kind, 1.0, None), Vec3::new(0.0, 1.0, 0.0), Vec3::NEG_Y);
+    // Points one unit below the light, at increasing angles from the center of the cone
+    let at_angle = |angle: f32| spot.incoming_light(Vec3::new(angle.tan(), 0.0, 0.0)).0.x * (1.0 + angle.tan().powi(2));
+    assert!((at_angle(0.0) - 1.0).abs() < 1e-5);
+    assert!((at_angle(0.19) - 1.0).abs() < 1e-5);
+    let between = at_angle(0.35);
+    assert!(between > 0.0 && between < 1.0);
+    assert_eq!(at_angle(0.51), 0.0);
+    assert_eq!(at_angle(1.0), 0.0);
+}
+
+#[test]
+fn directional_lights_dont_fall_off() {
+    let sun = placed(light(LightKind::Directional, 3.0, Some(1.0)), Vec3::ZERO, Vec3::new(0.0, -2.0, 0.0));
+    let (radiance, to_light) = sun.incoming_light(Vec3::new(100.0, -50.0, 20.0));
+    assert_eq!(radiance, Vec3::splat(3.0));
+    assert!((to_light - Vec3::Y).length() < 1e-5);
+}
+
+// Renders lights.gltf from the front and returns the RGBA bytes of the center pixel
+fn render_center(lights: Vec<PlacedLight>) -> [u8; 4] {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/lights.gltf")).unwrap();
+    renderer.set_lights(lights);
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    renderer.framebuffer()[8 + 8 * 16].to_le_bytes()
+}
+
+#[test]
+fn render_with_model_and_renderer_lights() {
+    // The surface has the default material, a white metal, and faces the camera
+    let surface = Surface {
+        base_color: Vec3::ONE,
+        metallic: 1.0,
+        roughness: 1.0,
+        normal: Vec3::Z,
+        occlusion: 1.0,
+        emissive: Vec3::ZERO,
+    };
+    let expected = |lights: &[PlacedLight]| {
+        let color = linear_to_srgb(shade_surface(lights, Vec3::ZERO, Vec3::Z, &surface)) * 255.0;
+        color.round().to_array().map(|channel| channel as u8)
+    };
+    let sun = placed(light(LightKind::Directional, PI / 4.0, None), Vec3::ZERO, Vec3::NEG_Z);
+
+    // The lamp and spot light are level with the surface, so only the sun lights it
+    let [r, g, b, a] = render_center(Vec::new());
+    assert_eq!(a, 255);
+    for (channel, expected) in [r, g, b].into_iter().zip(expected(std::slice::from_ref(&sun))) {
+        assert!(channel.abs_diff(expected) <= 1, "{channel} should be {expected}");
+    }
+
+    // Lights added to the renderer are added to the ones in the model
+    let extra = placed(light(LightKind::Directional, PI / 2.0, None), Vec3::ZERO, Vec3::NEG_Z);
+    let [r, g, b, _] = render_center(vec![extra.clone()]);
+    for (channel, expected) in [r, g, b].into_iter().zip(expected(&[sun, extra])) {
+        assert!(channel.abs_diff(expected) <= 1, "{channel} should be {expected}");
+    }
+}
@@ -0,0 +1,156 @@
+use std::io;
+use std::path::Path;
+
+use glam::{Quat, Vec3};
+use rust_render_metal::{AssetError, LoadOptions, Model, ModelHandle, ModelQueueEntry, RenderBackend, SoftwareRenderer, Transform};
+
+const WHITE: u32 = 0xFFFFFFFF;
+
+// A single point at the origin, with its position in buffer 0
+fn point_json(buffer: &str) -> String {
+    format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "mode": 0 }}] }}],
+            "buffers": [{buffer}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 12 }}],
+            "accessors": [{{ "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3", "min": [0, 0, 0], "max": [0, 0, 0] }}]
+        }}"#
+    )
+}
+
+fn point_bin() -> Vec<u8> {
+    [0.0f32; 3].iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+// Header, then a JSON chunk and a BIN chunk, both padded to 4 bytes
+fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+    let mut json = json.as_bytes().to_vec();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = bin.to_vec();
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"glTF");
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.extend_from_slice(&(12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+    for (chunk, kind) in [(&json, b"JSON"), (&bin, b"BIN\0")] {
+        bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(chunk);
+    }
+    bytes
+}
+
+fn no_files(uri: &str) -> io::Result<Vec<u8>> {
+    panic!("nothing should be resolved, but \"{uri}\" was");
+}
+
+fn draw(renderer: &mut SoftwareRenderer, model: ModelHandle) -> Vec<u32> {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    renderer.framebuffer().to_vec()
+}
+
+#[test]
+fn gltf_json_loads_from_a_slice() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let bytes = include_bytes!("assets/scenes.gltf");
+    let model = Model::load_gltf_from_slice(bytes, &mut no_files, &mut renderer, &LoadOptions::default()).unwrap();
+    let from_file = Model::load_gltf(Path::new("./tests/assets/scenes.gltf"), &mut renderer).unwrap();
+    assert_eq!(model.nodes.len(), from_file.nodes.len());
+    assert_eq!(model.scenes.len(), from_file.scenes.len());
+    assert_eq!(model.root_nodes, from_file.root_nodes);
+}
+
+#[test]
+fn glb_loads_from_a_slice_and_draws() {
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let bytes = glb(&point_json(r#"{ "byteLength": 12 }"#), &point_bin());
+    let model = renderer.load_model_from_slice(&bytes, &mut no_files, &LoadOptions::default()).unwrap();
+    let framebuffer = draw(&mut renderer, model);
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 1);
+    assert_eq!(framebuffer[8 + 8 * 16], WHITE);
+}
+
+#[test]
+fn glb_loads_from_a_reader() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let bytes = glb(&point_json(r#"{ "byteLength": 12 }"#), &point_bin());
+    let model = renderer.load_model_from_reader(&mut bytes.as_slice(), &mut no_files, &LoadOptions::default()).unwrap();
+    assert_eq!(renderer.model(model).unwrap().meshes[0][0].mesh.verts.len(), 1);
+}
+
+#[test]
+fn external_buffers_go_through_the_resolver() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let json = point_json(r#"{ "byteLength": 12, "uri": "point%20data.bin" }"#);
+    let mut requested = Vec::new();
+    let mut resolver = |uri: &str| {
+        requested.push(uri.to_string());
+        Ok(point_bin())
+    };
+    let model = Model::load_gltf_from_slice(json.as_bytes(), &mut resolver, &mut renderer, &LoadOptions::default()).unwrap();
+    assert_eq!(requested, ["point data.bin"]);
+    assert_eq!(model.meshes[0][0].mesh.verts[0].position, Vec3::ZERO);
+}
+
+#[test]
+fn external_images_go_through_the_resolver() {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, 1, 1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.write_header().unwrap().write_image_data(&[255, 0, 0, 255]).unwrap();
+
+    let json = point_json(r#"{ "byteLength": 12, "uri": "point.bin" }"#).replacen(
+        r#""buffers""#,
+        r#""materials": [{ "name": "red", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+           "textures": [{ "source": 0 }],
+           "images": [{ "uri": "textures/red.png" }],
+           "buffers""#,
+        1,
+    );
+    let mut resolver = |uri: &str| match uri {
+        "point.bin" => Ok(point_bin()),
+        "textures/red.png" => Ok(png.clone()),
+        _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+    };
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let model = Model::load_gltf_from_slice(json.as_bytes(), &mut resolver, &mut renderer, &LoadOptions::default()).unwrap();
+    assert!(model.materials[0].tex_alb.is_some());
+}
+
+#[test]
+fn resolver_errors_are_io_errors() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let json = point_json(r#"{ "byteLength": 12, "uri": "missing.bin" }"#);
+    let mut resolver = |_: &str| Err(io::Error::from(io::ErrorKind::NotFound));
+    let result = Model::load_gltf_from_slice(json.as_bytes(), &mut resolver, &mut renderer, &LoadOptions::default());
+    assert!(matches!(result, Err(AssetError::Io { path, .. }) if path == Path::new("missing.bin")));
+}
+
+#[test]
+fn short_buffers_are_malformed() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let bytes = glb(&point_json(r#"{ "byteLength": 12 }"#), &[0; 4]);
+    let result = renderer.load_model_from_slice(&bytes, &mut no_files, &LoadOptions::default());
+    assert!(matches!(result, Err(AssetError::MalformedData(_))));
+}
+
+#[test]
+fn invalid_bytes_are_parse_errors() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let result = renderer.load_model_from_slice(b"{ this is not json", &mut no_files, &LoadOptions::default());
+    assert!(matches!(result, Err(AssetError::Parse { .. })));
+    let result = renderer.load_model_from_slice(b"glTF\x02\0\0\0", &mut no_files, &LoadOptions::default());
+    assert!(matches!(result, Err(AssetError::Parse { .. })));
+}
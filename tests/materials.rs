@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use glam::{Quat, Vec3, Vec4};
+use rust_render_metal::{AlphaMode, Light, LightKind, Model, ModelQueueEntry, PlacedLight, RenderBackend, SoftwareRenderer, Transform};
+
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0xFF00FF00;
+const WHITE: u32 = 0xFFFFFFFF;
+const CLEAR: u32 = 0xFF331A1A;
+
+fn load() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/materials.gltf"), &mut renderer).unwrap()
+}
+
+#[test]
+fn primitives_refer_to_materials_by_index() {
+    let model = load();
+    let materials: Vec<Option<usize>> = model.meshes[0].iter().map(|primitive| primitive.material).collect();
+    assert_eq!(materials, [Some(0), Some(1), Some(2), None]);
+}
+
+#[test]
+fn materials_with_the_same_name_stay_separate() {
+    let model = load();
+    assert_eq!(model.materials.len(), 3);
+    assert_eq!(model.materials[0].name, "shared");
+    assert_eq!(model.materials[1].name, "shared");
+    assert_eq!(model.materials[0].scl_rgh, 0.25);
+    assert_eq!(model.materials[1].scl_rgh, 0.75);
+    assert_ne!(model.materials[0].tex_alb, model.materials[1].tex_alb);
+    assert_eq!(model.find_material("shared"), Some(0));
+    assert_eq!(model.find_material("missing"), None);
+}
+
+#[test]
+fn unnamed_materials_are_still_loaded() {
+    let model = load();
+    assert_eq!(model.materials[2].name, "untitled");
+    assert_eq!(model.material(Some(2)).scl_mtl, 0.5);
+}
+
+#[test]
+fn primitives_without_a_material_use_the_default() {
+    let model = load();
+    let material = model.material(model.meshes[0][3].material);
+    assert_eq!(material.scl_mtl, 1.0);
+    assert_eq!(material.scl_rgh, 1.0);
+    assert!(material.textures().next().is_none());
+}
+
+#[test]
+fn every_primitive_is_drawn_with_its_own_material() {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/materials.gltf")).unwrap();
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+
+    let framebuffer = renderer.framebuffer();
+    assert_eq!(framebuffer[4 + 8 * 16], RED);
+    assert_eq!(framebuffer[11 + 8 * 16], GREEN);
+    assert_eq!(framebuffer[8 + 4 * 16], WHITE);
+    assert_eq!(framebuffer[8 + 11 * 16], WHITE);
+}
+
+#[test]
+fn every_texture_slot_and_factor_is_loaded() {
+    let model = Model::load_gltf(Path::new("./tests/assets/pbr_material.gltf"), &mut SoftwareRenderer::new(4, 4)).unwrap();
+    let material = &model.materials[0];
+    assert_eq!(material.scl_alb, Vec4::new(0.5, 0.25, 1.0, 0.75));
+    assert_eq!((material.scl_mtl, material.scl_rgh), (0.2, 0.4));
+    assert_eq!((material.scl_nrm, material.scl_occ), (0.5, 0.3));
+    assert_eq!(material.scl_emm, Vec3::new(1.0, 0.5, 0.0));
+
+    let slots = [material.tex_alb, material.tex_mtl_rgh, material.tex_nrm, material.tex_occ, material.tex_emm].map(Option::unwrap);
+    let tex_coords = slots.map(|slot| slot.tex_coord);
+    assert_eq!(tex_coords, [0, 1, 1, 0, 0]);
+    assert_eq!(material.alpha_mode, AlphaMode::Opaque);
+    assert!(!material.double_sided);
+}
+
+#[test]
+fn images_are_uploaded_once() {
+    let model = Model::load_gltf(Path::new("./tests/assets/pbr_material.gltf"), &mut SoftwareRenderer::new(4, 4)).unwrap();
+    let material = &model.materials[0];
+    // Occlusion shares the metallic/roughness image, emissive uses the base color image through another texture
+    assert_eq!(material.tex_occ.unwrap().texture, material.tex_mtl_rgh.unwrap().texture);
+    assert_eq!(material.tex_emm.unwrap().texture, material.tex_alb.unwrap().texture);
+    assert_eq!(model.textures().len(), 3);
+}
+
+#[test]
+fn shared_textures_are_freed_once() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let model = renderer.load_model(Path::new("./tests/assets/pbr_material.gltf")).unwrap();
+    // Freeing a texture twice would make the second one stale
+    assert_eq!(renderer.unload_model(model), Ok(()));
+}
+
+#[test]
+fn alpha_mode_and_double_sided_are_loaded() {
+    let model = Model::load_gltf(Path::new("./tests/assets/pbr_material.gltf"), &mut SoftwareRenderer::new(4, 4)).unwrap();
+    let material = &model.materials[1];
+    // The cutoff defaults to 0.5
+    assert_eq!(material.alpha_mode, AlphaMode::Mask(0.5));
+    assert!(material.double_sided);
+    assert!(material.textures().next().is_none());
+    assert_eq!(material.scl_alb, Vec4::ONE);
+}
+
+// Renders alpha_modes.gltf, a 3x2 grid of quads: opaque, blend and mask with a 0.6 cutoff on top, mask with a 0.4
+// cutoff, single sided and double sided below. The top four are red at half alpha, the bottom two face away.
+fn render_alpha_modes(lights: Vec<PlacedLight>) -> [[u8; 4]; 6] {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/alpha_modes.gltf")).unwrap();
+    renderer.set_lights(lights);
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+
+    let framebuffer = renderer.framebuffer();
+    let centers = [(5, 6), (8, 6), (10, 6), (5, 9), (8, 9), (11, 9)];
+    centers.map(|(x, y)| framebuffer[x + y * 16].to_le_bytes())
+}
+
+#[test]
+fn alpha_modes_are_honored() {
+    let [opaque, blend, mask_cut, mask_kept, _, _] = render_alpha_modes(Vec::new());
+    // Opaque ignores the base color alpha
+    assert_eq!(opaque, RED.to_le_bytes());
+    // Blend mixes half of the red with half of the clear color
+    assert_eq!(blend, [140, 13, 26, 255]);
+    // Mask either discards the pixel or draws it opaque
+    assert_eq!(mask_cut, CLEAR.to_le_bytes());
+    assert_eq!(mask_kept, RED.to_le_bytes());
+}
+
+#[test]
+fn back_faces_are_culled_unless_double_sided() {
+    let sun = Light {
+        name: "sun".to_string(),
+        kind: LightKind::Directional,
+        color: Vec3::ONE,
+        intensity: 1.0,
+        range: None,
+    };
+    // The light comes from the camera, so it only reaches the back faces if their normals are flipped
+    let lights = vec![PlacedLight { light: sun, position: Vec3::ZERO, direction: Vec3::NEG_Z }];
+    let [_, _, _, _, single_sided, double_sided] = render_alpha_modes(lights);
+    assert_eq!(single_sided, CLEAR.to_le_bytes());
+    assert!(double_sided[0] > 100, "{double_sided:?}");
+}
@@ -0,0 +1,31 @@
+use glam::{UVec4, Vec2, Vec3, Vec4};
+use rust_render_metal::mesh::{Indices, PrimitiveType};
+use rust_render_metal::structs::Vertex;
+use rust_render_metal::Mesh;
+
+fn mesh_with_vertex_count(vertex_count: usize) -> Mesh {
+    let vertex = Vertex {
+        position: Vec3::ZERO,
+        normal: Vec3::ZERO,
+        tangent: Vec4::ZERO,
+        color: Vec4::ONE,
+        uv0: Vec2::ZERO,
+        uv1: Vec2::ZERO,
+        joints: UVec4::ZERO,
+        weights: Vec4::ZERO,
+    };
+    let last = vertex_count as u32 - 1;
+    Mesh {
+        verts: vec![vertex; vertex_count],
+        indices: Indices::new(vec![0, last / 2, last], vertex_count),
+        primitive_type: PrimitiveType::Triangles,
+        morph_targets: Vec::new(),
+        buffer: None,
+    }
+}
+
+#[test]
+fn index_type_depends_on_vertex_count() {
+    assert!(matches!(mesh_with_vertex_count(65536).indices, Indices::U16(_)));
+    assert!(matches!(mesh_with_vertex_count(65537).indices, Indices::U32(_)));
+}
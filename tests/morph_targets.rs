@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use glam::{Quat, Vec3, Vec4};
+use rust_render_metal::morph::blend_vertices;
+use rust_render_metal::{AnimationPlayer, AssetError, Model, ModelQueueEntry, RenderBackend, SoftwareRenderer, Transform};
+
+fn load_morphs() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/morph_targets.gltf"), &mut renderer).unwrap()
+}
+
+fn blended_position(model: &Model, node: &str) -> Vec3 {
+    let mesh = &model.meshes[0][0].mesh;
+    let weights = &model.nodes[model.find_node(node).unwrap()].weights;
+    blend_vertices(&mesh.verts, &mesh.morph_targets, weights)[0].position
+}
+
+#[test]
+fn targets_are_loaded_per_primitive() {
+    let model = load_morphs();
+    let targets = &model.meshes[0][0].mesh.morph_targets;
+    assert_eq!(targets.len(), 2);
+    assert_eq!(targets[0].positions, [Vec3::X]);
+    assert_eq!(targets[0].normals, [Vec3::Y]);
+    assert!(targets[0].tangents.is_empty());
+    assert_eq!(targets[1].positions, [Vec3::Y]);
+    assert_eq!(targets[1].tangents, [Vec3::X]);
+}
+
+#[test]
+fn nodes_use_the_mesh_weights_unless_they_have_their_own() {
+    let model = load_morphs();
+    assert_eq!(model.nodes[model.find_node("default").unwrap()].weights, [0.5, 0.0]);
+    assert_eq!(model.nodes[model.find_node("override").unwrap()].weights, [0.0, 1.0]);
+}
+
+#[test]
+fn deltas_are_scaled_by_the_weights() {
+    let model = load_morphs();
+    let mesh = &model.meshes[0][0].mesh;
+    let blended = blend_vertices(&mesh.verts, &mesh.morph_targets, &[0.5, 0.25]);
+    assert_eq!(blended[0].position, Vec3::new(0.5, 0.25, 0.0));
+    assert_eq!(blended[0].normal, Vec3::new(0.0, 0.5, 1.0));
+    assert_eq!(blended[0].tangent, Vec4::new(0.25, 0.0, 0.0, 0.0));
+
+    // Missing weights count as 0
+    assert_eq!(blend_vertices(&mesh.verts, &mesh.morph_targets, &[])[0].position, Vec3::ZERO);
+}
+
+#[test]
+fn animations_drive_the_weights() {
+    let mut model = load_morphs();
+    let mut player = AnimationPlayer::new(model.find_animation("smile").unwrap());
+    player.looping = false;
+    player.update(0.5, &mut model);
+    assert_eq!(blended_position(&model, "default"), Vec3::new(0.5, 0.5, 0.0));
+    assert_eq!(blended_position(&model, "override"), Vec3::Y);
+}
+
+#[test]
+fn renderer_draws_blended_vertices() {
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/morph_targets.gltf")).unwrap();
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    renderer.update_camera(&Transform {
+        translation: Vec3::new(0.0, 0.0, 5.0),
+        ..identity
+    });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+
+    // The unmorphed point would be in the center, the two nodes moved it to (0.5, 0, 0) and (0, 1, 0)
+    let white = |x: usize, y: usize| renderer.framebuffer()[x + y * 16] == 0xFFFFFFFF;
+    assert!(!white(8, 8));
+    assert!(white(9, 8));
+    assert!(white(8, 4));
+}
+
+#[test]
+fn deltas_must_match_the_vertex_count() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let result = renderer.load_model(Path::new("./tests/assets/morph_bad_deltas.gltf"));
+    assert!(matches!(result, Err(AssetError::MalformedData(_))));
+}
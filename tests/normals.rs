@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use glam::{UVec4, Vec2, Vec3, Vec4};
+use rust_render_metal::mesh::{Indices, NormalMode, NormalWeighting, PrimitiveType};
+use rust_render_metal::morph::MorphTarget;
+use rust_render_metal::structs::Vertex;
+use rust_render_metal::{LoadOptions, Mesh, Model, SoftwareRenderer};
+
+// A tent of two triangles that meet at a right angle along the X axis. The one facing +Y is twice as big as the one facing +Z,
+// and has its own copy of the vertex at the origin, like a UV seam.
+fn load_tent(normals: NormalMode) -> Mesh {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let options = LoadOptions { normals, ..Default::default() };
+    let mut model = Model::load_gltf_with_options(Path::new("./tests/assets/no_normals.gltf"), &mut renderer, &options).unwrap();
+    model.meshes.remove(0).remove(0).mesh
+}
+
+fn smooth(hard_edge_angle: f32, weighting: NormalWeighting) -> NormalMode {
+    NormalMode::Smooth { hard_edge_angle, weighting }
+}
+
+// The normals of every triangle corner, in index order
+fn corner_normals(mesh: &Mesh) -> Vec<Vec3> {
+    mesh.indices.iter().map(|index| mesh.verts[index as usize].normal).collect()
+}
+
+fn assert_near(actual: Vec3, expected: Vec3) {
+    assert!((actual - expected).length() < 1e-5, "{actual} should be {expected}");
+}
+
+#[test]
+fn missing_normals_are_flat_by_default() {
+    let mesh = load_tent(NormalMode::default());
+    assert_eq!(mesh.verts.len(), 6);
+    let normals = corner_normals(&mesh);
+    for corner in 0..3 {
+        assert_near(normals[corner], Vec3::Z);
+        assert_near(normals[corner + 3], Vec3::Y);
+    }
+    // The rest of the vertex is kept when it's split
+    assert_eq!(mesh.verts[mesh.indices.get(4) as usize].uv0, Vec2::new(0.0, 1.0));
+}
+
+#[test]
+fn smooth_normals_weighted_by_area() {
+    let mesh = load_tent(smooth(PI * 0.75, NormalWeighting::Area));
+    let normals = corner_normals(&mesh);
+    let shared = Vec3::new(0.0, 2.0, 1.0).normalize();
+    // The origin (on both sides of the seam) and (1, 0, 0) are shared by both triangles
+    assert_near(normals[0], shared);
+    assert_near(normals[1], shared);
+    assert_near(normals[3], shared);
+    assert_near(normals[5], shared);
+    assert_near(normals[2], Vec3::Z);
+    assert_near(normals[4], Vec3::Y);
+    // Nothing had to be split
+    assert_eq!(mesh.verts.len(), 5);
+}
+
+#[test]
+fn smooth_normals_weighted_by_angle() {
+    let mesh = load_tent(smooth(PI * 0.75, NormalWeighting::Angle));
+    let normals = corner_normals(&mesh);
+    // Both triangles have a right angle at the origin
+    assert_near(normals[0], Vec3::new(0.0, 1.0, 1.0).normalize());
+    assert_near(normals[3], Vec3::new(0.0, 1.0, 1.0).normalize());
+    // At (1, 0, 0), the corner facing +Z is 45 degrees and the one facing +Y is atan(2)
+    let expected = (Vec3::Z * PI / 4.0 + Vec3::Y * 2.0f32.atan()).normalize();
+    assert_near(normals[1], expected);
+    assert_near(normals[5], expected);
+}
+
+#[test]
+fn sharp_edges_stay_hard() {
+    let mesh = load_tent(smooth(PI / 3.0, NormalWeighting::Area));
+    assert_eq!(corner_normals(&mesh), corner_normals(&load_tent(NormalMode::Flat)));
+    assert_eq!(mesh.verts.len(), 6);
+}
+
+#[test]
+fn generated_normals_get_tangents() {
+    let mesh = load_tent(NormalMode::Flat);
+    for vertex in &mesh.verts {
+        assert!(vertex.tangent.truncate().dot(vertex.normal).abs() < 1e-5);
+        assert!((vertex.tangent.truncate().length() - 1.0).abs() < 1e-5);
+    }
+}
+
+fn vertex(position: Vec3) -> Vertex {
+    Vertex {
+        position,
+        normal: Vec3::ZERO,
+        tangent: Vec4::ZERO,
+        color: Vec4::ONE,
+        uv0: Vec2::ZERO,
+        uv1: Vec2::ZERO,
+        joints: UVec4::ZERO,
+        weights: Vec4::ZERO,
+    }
+}
+
+#[test]
+fn split_vertices_keep_their_morph_deltas() {
+    let verts = vec![vertex(Vec3::ZERO), vertex(Vec3::X), vertex(Vec3::Y), vertex(Vec3::Z)];
+    let mut mesh = Mesh {
+        indices: Indices::new(vec![0, 1, 2, 0, 3, 1], verts.len()),
+        verts,
+        primitive_type: PrimitiveType::Triangles,
+        morph_targets: vec![MorphTarget {
+            positions: vec![Vec3::splat(0.0), Vec3::splat(1.0), Vec3::splat(2.0), Vec3::splat(3.0)],
+            normals: Vec::new(),
+            tangents: Vec::new(),
+        }],
+        buffer: None,
+    };
+    mesh.generate_normals(NormalMode::Flat);
+    assert_eq!(mesh.verts.len(), 6);
+    assert!(mesh.morph_targets[0].normals.is_empty());
+    for index in mesh.indices.iter() {
+        let vertex = &mesh.verts[index as usize];
+        let original = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z].iter().position(|position| *position == vertex.position).unwrap();
+        assert_eq!(mesh.morph_targets[0].positions[index as usize], Vec3::splat(original as f32));
+    }
+}
+
+#[test]
+fn only_triangles_get_normals() {
+    let mut mesh = Mesh {
+        indices: Indices::new(vec![0, 1], 2),
+        verts: vec![vertex(Vec3::ZERO), vertex(Vec3::X)],
+        primitive_type: PrimitiveType::Lines,
+        morph_targets: Vec::new(),
+        buffer: None,
+    };
+    mesh.generate_normals(NormalMode::Flat);
+    assert_eq!(mesh.verts.len(), 2);
+    assert!(mesh.verts.iter().all(|vertex| vertex.normal == Vec3::ZERO));
+}
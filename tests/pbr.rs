@@ -0,0 +1,181 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use glam::{Quat, Vec3, Vec4};
+use rust_render_metal::pbr::{brdf, linear_to_srgb, perturb_normal, shade_surface, srgb_to_linear, Surface};
+use rust_render_metal::{Light, LightKind, ModelQueueEntry, PlacedLight, RenderBackend, SoftwareRenderer, Transform};
+
+fn surface(base_color: Vec3, metallic: f32, roughness: f32) -> Surface {
+    Surface {
+        base_color,
+        metallic,
+        roughness,
+        normal: Vec3::Z,
+        occlusion: 1.0,
+        emissive: Vec3::ZERO,
+    }
+}
+
+fn sun(direction: Vec3, intensity: f32) -> PlacedLight {
+    PlacedLight {
+        light: Light {
+            name: "sun".to_string(),
+            kind: LightKind::Directional,
+            color: Vec3::ONE,
+            intensity,
+            range: None,
+        },
+        position: Vec3::ZERO,
+        direction,
+    }
+}
+
+fn assert_near(actual: Vec3, expected: Vec3) {
+    assert!((actual - expected).length() < 1e-4, "{actual} should be {expected}");
+}
+
+// A direction at the given angle from the normal, rotated around it
+fn direction(polar: f32, azimuth: f32) -> Vec3 {
+    Vec3::new(polar.sin() * azimuth.cos(), polar.sin() * azimuth.sin(), polar.cos())
+}
+
+// How much of the light from to_view is reflected in total, by integrating the BRDF over the hemisphere
+fn directional_albedo(surface: &Surface, to_view: Vec3) -> f32 {
+    let (polar_steps, azimuth_steps) = (256, 256);
+    let mut total = 0.0;
+    for i in 0..polar_steps {
+        let polar = (i as f32 + 0.5) / polar_steps as f32 * PI / 2.0;
+        for j in 0..azimuth_steps {
+            let azimuth = (j as f32 + 0.5) / azimuth_steps as f32 * 2.0 * PI;
+            let to_light = direction(polar, azimuth);
+            let solid_angle = polar.sin() * (PI / 2.0 / polar_steps as f32) * (2.0 * PI / azimuth_steps as f32);
+            total += brdf(surface, to_view, to_light).x * to_light.z * solid_angle;
+        }
+    }
+    total
+}
+
+#[test]
+fn head_on_matches_the_spec_formulas() {
+    let base_color = Vec3::new(0.5, 0.25, 1.0);
+    let roughness: f32 = 0.5;
+    let alpha = roughness * roughness;
+    // With the light and view along the normal, GGX is 1 / (pi * alpha^2), the visibility term is 1/4 and Fresnel is f0
+    let specular = 1.0 / (PI * alpha * alpha) * 0.25;
+
+    let dielectric = brdf(&surface(base_color, 0.0, roughness), Vec3::Z, Vec3::Z);
+    assert_near(dielectric, Vec3::splat(0.04 * specular) + 0.96 * base_color / PI);
+
+    let metal = brdf(&surface(base_color, 1.0, roughness), Vec3::Z, Vec3::Z);
+    assert_near(metal, base_color * specular);
+}
+
+#[test]
+fn brdf_is_reciprocal() {
+    let surface = surface(Vec3::new(0.8, 0.6, 0.4), 0.3, 0.4);
+    for (a, b) in [(direction(0.3, 0.0), direction(1.1, 2.0)), (direction(1.4, 1.0), direction(0.7, -0.5)), (Vec3::Z, direction(0.9, 3.0))] {
+        assert_near(brdf(&surface, a, b), brdf(&surface, b, a));
+    }
+}
+
+#[test]
+fn light_from_below_the_surface_is_not_reflected() {
+    let surface = surface(Vec3::ONE, 0.0, 0.5);
+    assert_eq!(brdf(&surface, Vec3::Z, direction(PI * 0.6, 0.0)), Vec3::ZERO);
+    assert_eq!(shade_surface(&[sun(Vec3::Z, 10.0)], Vec3::ZERO, Vec3::Z, &surface), Vec3::ZERO);
+}
+
+#[test]
+fn metals_have_no_diffuse_reflection() {
+    // A black metal has f0 = 0, so it only reflects at grazing angles where Fresnel goes to 1
+    let black_metal = surface(Vec3::ZERO, 1.0, 1.0);
+    assert_eq!(brdf(&black_metal, Vec3::Z, Vec3::Z), Vec3::ZERO);
+    assert!(brdf(&black_metal, direction(1.5, 0.0), direction(1.5, PI)).x > 0.0);
+}
+
+#[test]
+fn energy_is_conserved() {
+    for (metallic, roughness) in [(0.0, 1.0), (0.0, 0.5), (1.0, 0.3), (1.0, 1.0)] {
+        let surface = surface(Vec3::ONE, metallic, roughness);
+        for polar in [0.0, 0.6, 1.2] {
+            let albedo = directional_albedo(&surface, direction(polar, 0.0));
+            // Single scattering GGX loses some energy on rough surfaces, but never creates any. The spec's dielectric mix
+            // weighs the diffuse lobe with the Fresnel of the half vector, which overshoots a little at grazing angles.
+            let limit = if metallic == 1.0 { 1.0 + 1e-3 } else { 1.05 };
+            assert!(albedo <= limit && albedo > 0.25, "metallic {metallic}, roughness {roughness}, polar {polar}: {albedo}");
+        }
+    }
+}
+
+#[test]
+fn smoother_surfaces_have_sharper_highlights() {
+    let to_view = direction(0.5, 0.0);
+    let mirror = direction(0.5, PI);
+    let off_mirror = direction(0.9, PI);
+    let highlight = |roughness: f32, to_light: Vec3| brdf(&surface(Vec3::ONE, 1.0, roughness), to_view, to_light).x;
+    assert!(highlight(0.2, mirror) > highlight(0.6, mirror));
+    assert!(highlight(0.2, off_mirror) < highlight(0.6, off_mirror));
+    // Perfectly smooth surfaces are clamped, so they still have a finite highlight
+    assert!(highlight(0.0, mirror).is_finite());
+}
+
+#[test]
+fn lights_add_up_and_occlusion_only_darkens_reflected_light() {
+    let mut surface = surface(Vec3::new(1.0, 0.5, 0.25), 0.0, 0.7);
+    let to_view = direction(0.2, 1.0);
+    let (a, b) = (sun(-direction(0.4, 0.0), 2.0), sun(-direction(1.0, 2.0), 3.0));
+    let both = shade_surface(&[a.clone(), b.clone()], Vec3::ZERO, to_view, &surface);
+    let separate = shade_surface(std::slice::from_ref(&a), Vec3::ZERO, to_view, &surface) + shade_surface(&[b], Vec3::ZERO, to_view, &surface);
+    assert_near(both, separate);
+
+    let lit = shade_surface(std::slice::from_ref(&a), Vec3::ZERO, to_view, &surface);
+    surface.occlusion = 0.25;
+    surface.emissive = Vec3::new(0.0, 1.0, 0.0);
+    assert_near(shade_surface(&[a], Vec3::ZERO, to_view, &surface), lit * 0.25 + surface.emissive);
+}
+
+#[test]
+fn normal_maps_bend_the_normal_in_tangent_space() {
+    let tangent = Vec4::new(1.0, 0.0, 0.0, 1.0);
+    // A flat normal map leaves the normal alone
+    assert_near(perturb_normal(Vec3::Z, tangent, Vec3::new(0.5, 0.5, 1.0), 1.0), Vec3::Z);
+    // Red and green follow the tangent and bitangent, where the bitangent flips with the handedness
+    assert_near(perturb_normal(Vec3::Z, tangent, Vec3::new(1.0, 0.5, 0.5), 1.0), Vec3::X);
+    assert_near(perturb_normal(Vec3::Z, tangent, Vec3::new(0.5, 1.0, 0.5), 1.0), Vec3::Y);
+    assert_near(perturb_normal(Vec3::Z, Vec4::new(1.0, 0.0, 0.0, -1.0), Vec3::new(0.5, 1.0, 0.5), 1.0), Vec3::NEG_Y);
+    // The scale only affects X and Y, so 0 turns the normal map off
+    assert_near(perturb_normal(Vec3::Z, tangent, Vec3::new(1.0, 1.0, 0.5), 0.0), Vec3::Z);
+    let half = perturb_normal(Vec3::Z, tangent, Vec3::new(1.0, 0.5, 1.0), 0.5);
+    assert_near(half, Vec3::new(0.5, 0.0, 1.0).normalize());
+    // Without a tangent, there is nothing to bend the normal with
+    assert_near(perturb_normal(Vec3::Z * 2.0, Vec4::ZERO, Vec3::new(1.0, 0.5, 0.5), 1.0), Vec3::Z);
+}
+
+#[test]
+fn srgb_conversion_round_trips() {
+    assert_near(srgb_to_linear(Vec3::new(0.0, 0.5, 1.0)), Vec3::new(0.0, 0.21404, 1.0));
+    assert_near(linear_to_srgb(Vec3::new(0.0, 0.21404, 1.0)), Vec3::new(0.0, 0.5, 1.0));
+    for value in [0.001, 0.01, 0.04, 0.2, 0.7, 0.99] {
+        assert_near(linear_to_srgb(srgb_to_linear(Vec3::splat(value))), Vec3::splat(value));
+    }
+}
+
+#[test]
+fn emission_is_drawn_with_the_material_textures() {
+    // The point has no normal, so it doesn't reflect the light, but the emissive texture and factor still light it up
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/pbr_material.gltf")).unwrap();
+    renderer.set_lights(vec![sun(Vec3::NEG_Z, 1.0)]);
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+
+    // Red emissive texture times (1, 0.5, 0), the material is opaque so the base color alpha is ignored
+    assert_eq!(renderer.framebuffer()[8 + 8 * 16].to_le_bytes(), [255, 0, 0, 255]);
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use glam::{Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
+use rust_render_metal::mesh::{Indices, Primitive, PrimitiveType};
+use rust_render_metal::structs::Vertex;
+use rust_render_metal::{LoadOptions, Mesh, Model, ModelQueueEntry, Node, RenderBackend, SoftwareRenderer, Transform};
+
+const WHITE: u32 = 0xFFFFFFFF;
+
+fn load_modes() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/primitive_modes.gltf"), &mut renderer).unwrap()
+}
+
+fn mesh_for(model: &Model, material: &str) -> (PrimitiveType, Vec<u32>) {
+    let primitive = model.meshes[0].iter().find(|primitive| primitive.material == model.find_material(material)).unwrap();
+    (primitive.mesh.primitive_type, primitive.mesh.indices.to_u32())
+}
+
+#[test]
+fn lists_and_points_are_kept() {
+    let model = load_modes();
+    assert_eq!(mesh_for(&model, "points"), (PrimitiveType::Points, vec![0, 1, 2, 3]));
+    assert_eq!(mesh_for(&model, "lines"), (PrimitiveType::Lines, vec![0, 1, 2, 3]));
+    assert_eq!(mesh_for(&model, "triangles"), (PrimitiveType::Triangles, vec![0, 1, 2, 2, 1, 3]));
+}
+
+#[test]
+fn line_strips_and_loops_become_line_lists() {
+    let model = load_modes();
+    assert_eq!(mesh_for(&model, "line_strip"), (PrimitiveType::Lines, vec![0, 1, 1, 2, 2, 3]));
+    assert_eq!(mesh_for(&model, "line_loop"), (PrimitiveType::Lines, vec![0, 1, 1, 2, 2, 3, 3, 0]));
+}
+
+#[test]
+fn triangle_strips_and_fans_become_triangle_lists() {
+    let model = load_modes();
+    // Every other strip triangle is flipped to keep the winding order consistent
+    assert_eq!(mesh_for(&model, "triangle_strip"), (PrimitiveType::Triangles, vec![0, 1, 2, 1, 3, 2]));
+    assert_eq!(mesh_for(&model, "triangle_fan"), (PrimitiveType::Triangles, vec![1, 2, 0, 2, 3, 0]));
+}
+
+// Renders a single mesh at z = -1 with the camera at the origin, on a 16x16 framebuffer
+fn render(positions: &[Vec3], primitive_type: PrimitiveType) -> Vec<u32> {
+    let verts = positions
+        .iter()
+        .map(|position| Vertex {
+            position: *position,
+            normal: Vec3::Z,
+            tangent: Vec4::ZERO,
+            color: Vec4::ONE,
+            uv0: Vec2::ZERO,
+            uv1: Vec2::ZERO,
+            joints: UVec4::ZERO,
+            weights: Vec4::ZERO,
+        })
+        .collect::<Vec<_>>();
+    let mut mesh = Mesh {
+        indices: Indices::new((0..verts.len() as u32).collect(), verts.len()),
+        verts,
+        primitive_type,
+        morph_targets: Vec::new(),
+        buffer: None,
+    };
+
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    renderer.upload_vertex_buffer(&mut mesh);
+    let mut model = Model::new();
+    model.meshes.push(vec![Primitive { mesh, material: None }]);
+    model.nodes.push(Node {
+        name: "points".to_string(),
+        transform: identity,
+        children: Vec::new(),
+        mesh: Some(0),
+        skin: None,
+        camera: None,
+        light: None,
+        weights: Vec::new(),
+        world_matrix: Mat4::IDENTITY,
+    });
+    model.root_nodes.push(0);
+    let model = renderer.store_model(model);
+
+    renderer.update_camera(&identity);
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    renderer.framebuffer().to_vec()
+}
+
+#[test]
+fn points_cover_a_single_pixel() {
+    let framebuffer = render(&[Vec3::new(-0.2, -0.2, -1.0), Vec3::new(0.0, 0.0, -1.0)], PrimitiveType::Points);
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 2);
+    assert_eq!(framebuffer[4 + 11 * 16], WHITE);
+    assert_eq!(framebuffer[8 + 8 * 16], WHITE);
+}
+
+#[test]
+fn lines_cover_every_pixel_along_them() {
+    let framebuffer = render(&[Vec3::new(-0.2, 0.0, -1.0), Vec3::new(0.2, 0.0, -1.0)], PrimitiveType::Lines);
+    for x in 0..16 {
+        let covered = (4..=11).contains(&x);
+        assert_eq!(framebuffer[x + 8 * 16] == WHITE, covered, "pixel {x}");
+    }
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 8);
+}
+
+#[test]
+fn triangle_lists_without_a_whole_triangle_are_not_uploaded() {
+    // Three vertices, but only 2 indices, so the triangle list is truncated to nothing
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "mode": 4 }] }],
+        "buffers": [{ "byteLength": 40, "uri": "triangle.bin" }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }, { "buffer": 0, "byteOffset": 36, "byteLength": 4 }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [0, 0, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 2, "type": "SCALAR" }
+        ]
+    }"#;
+    let mut resolver = |_: &str| Ok(vec![0; 40]);
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model_from_slice(json.as_bytes(), &mut resolver, &LoadOptions::default()).unwrap();
+    let mesh = &renderer.model(model).unwrap().meshes[0][0].mesh;
+    assert!(mesh.indices.is_empty());
+    assert!(mesh.is_empty());
+    assert_eq!(mesh.buffer, None);
+
+    // Drawing it is skipped, rather than drawing nothing from an empty buffer
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    assert!(!renderer.framebuffer().contains(&WHITE));
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use glam::{Quat, Vec3};
+use rust_render_metal::texture::{FilterMode, WrapMode};
+use rust_render_metal::{Model, ModelQueueEntry, RenderBackend, Sampler, SoftwareRenderer, Transform};
+
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0xFF00FF00;
+
+fn load() -> Model {
+    Model::load_gltf(Path::new("./tests/assets/samplers.gltf"), &mut SoftwareRenderer::new(4, 4)).unwrap()
+}
+
+fn sampler(model: &Model, material: &str) -> Sampler {
+    let material = model.find_material(material).unwrap();
+    model.materials[material].tex_alb.unwrap().sampler
+}
+
+#[test]
+fn gltf_samplers_are_imported() {
+    let model = load();
+    let linear = sampler(&model, "linear");
+    assert_eq!(linear.filter_mode_mag, FilterMode::Linear);
+    // LINEAR_MIPMAP_NEAREST filters linearly within a mip level, and picks the nearest level
+    assert_eq!(linear.filter_mode_min, FilterMode::Linear);
+    assert_eq!(linear.filter_mode_mipmap, FilterMode::Point);
+    assert!(linear.mipmap_enabled);
+    assert_eq!((linear.wrap_mode_s, linear.wrap_mode_t), (WrapMode::Mirror, WrapMode::Clamp));
+
+    let clamp = sampler(&model, "clamp");
+    assert_eq!((clamp.filter_mode_mag, clamp.filter_mode_min), (FilterMode::Point, FilterMode::Point));
+    assert!(!clamp.mipmap_enabled);
+    assert_eq!((clamp.wrap_mode_s, clamp.wrap_mode_t), (WrapMode::Clamp, WrapMode::Repeat));
+}
+
+#[test]
+fn textures_without_a_sampler_repeat_unfiltered() {
+    let model = load();
+    assert_eq!(sampler(&model, "no_sampler"), Sampler::new());
+    assert_eq!(Sampler::new().wrap_mode_s, WrapMode::Repeat);
+    assert_eq!(Sampler::new().filter_mode_mag, FilterMode::Point);
+}
+
+#[test]
+fn samplers_are_honored_when_drawing() {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/samplers.gltf")).unwrap();
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+
+    // The texture is red on the left and green on the right. U = 1.25 repeats to the red texel, or clamps to the green one.
+    let framebuffer = renderer.framebuffer();
+    assert_eq!(framebuffer[4 + 8 * 16], RED);
+    assert_eq!(framebuffer[11 + 8 * 16], GREEN);
+    // U = 0.5 is right between both texel centers, so linear filtering blends them evenly
+    assert_eq!(framebuffer[8 + 4 * 16].to_le_bytes(), [128, 128, 0, 255]);
+}
+
+#[test]
+fn minified_textures_are_filtered_with_their_mips() {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/minified.gltf")).unwrap();
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+
+    // The checkerboard repeats many times per pixel. On the left, trilinear filtering averages it to grey,
+    // while on the right, nearest filtering without mips picks either a black or a white texel.
+    let framebuffer = renderer.framebuffer();
+    for x in 5..7 {
+        let [r, g, b, _] = framebuffer[x + 8 * 16].to_le_bytes();
+        assert!((r as i32 - 128).abs() <= 1 && r == g && g == b, "{r} {g} {b}");
+    }
+    for x in 9..11 {
+        let [r, g, b, _] = framebuffer[x + 8 * 16].to_le_bytes();
+        assert!((r == 0 || r == 255) && r == g && g == b, "{r} {g} {b}");
+    }
+}
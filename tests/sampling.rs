@@ -0,0 +1,325 @@
+use glam::{UVec2, Vec2, Vec4};
+use rust_render_metal::sampling::{level_of_detail, MipChain};
+use rust_render_metal::texture::{FilterMode, WrapMode};
+use rust_render_metal::{Sampler, Texture};
+
+// A greyscale texture, with every texel's brightness given in 0..255, row by row
+fn texture(width: usize, height: usize, values: &[u8]) -> Texture {
+    assert_eq!(values.len(), width * height);
+    Texture {
+        gl_id: 0,
+        width,
+        height,
+        depth: 4,
+        data: values.iter().map(|value| u32::from_le_bytes([*value, *value, *value, 255])).collect(),
+    }
+}
+
+fn grey(value: f32) -> Vec4 {
+    Vec4::new(value / 255.0, value / 255.0, value / 255.0, 1.0)
+}
+
+fn assert_near(actual: Vec4, expected: Vec4) {
+    assert!((actual - expected).abs().max_element() < 1e-5, "{actual} should be {expected}");
+}
+
+fn sampler(filter: FilterMode, wrap_mode: WrapMode) -> Sampler {
+    Sampler {
+        filter_mode_mag: filter,
+        filter_mode_min: filter,
+        filter_mode_mipmap: filter,
+        wrap_mode_s: wrap_mode,
+        wrap_mode_t: wrap_mode,
+        mipmap_enabled: false,
+    }
+}
+
+// The UV of the center of a texel
+fn center(x: usize, y: usize, texture: &Texture) -> Vec2 {
+    Vec2::new((x as f32 + 0.5) / texture.width as f32, (y as f32 + 0.5) / texture.height as f32)
+}
+
+#[test]
+fn wrap_modes_map_every_index_onto_the_texture() {
+    let indices: Vec<isize> = (-6..9).collect();
+    let wrapped = |mode: WrapMode| indices.iter().map(|index| mode.apply(*index, 3)).collect::<Vec<usize>>();
+    // -6 -5 -4 -3 -2 -1  0  1  2  3  4  5  6  7  8
+    assert_eq!(wrapped(WrapMode::Repeat), [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2]);
+    assert_eq!(wrapped(WrapMode::Mirror), [0, 1, 2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0, 1, 2]);
+    assert_eq!(wrapped(WrapMode::Clamp), [0, 0, 0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2]);
+    // A single texel wide texture always wraps onto that texel
+    for mode in [WrapMode::Repeat, WrapMode::Mirror, WrapMode::Clamp] {
+        assert!(indices.iter().all(|index| mode.apply(*index, 1) == 0));
+    }
+}
+
+#[test]
+fn wrap_modes_apply_to_each_axis_separately() {
+    let texture = texture(2, 2, &[0, 64, 128, 192]);
+    let mut sampler = sampler(FilterMode::Point, WrapMode::Repeat);
+    sampler.wrap_mode_t = WrapMode::Clamp;
+    assert_near(sampler.texel(&texture, 2, 0), grey(0.0));
+    assert_near(sampler.texel(&texture, -1, 0), grey(64.0));
+    assert_near(sampler.texel(&texture, 0, 2), grey(128.0));
+    assert_near(sampler.texel(&texture, 0, -1), grey(0.0));
+}
+
+#[test]
+fn point_sampling_picks_the_texel_under_the_uv() {
+    let values: Vec<u8> = (0..16).map(|value| value * 16).collect();
+    let texture = texture(4, 4, &values);
+    let sampler = sampler(FilterMode::Point, WrapMode::Clamp);
+    for y in 0..4 {
+        for x in 0..4 {
+            let expected = grey(values[x + y * 4] as f32);
+            assert_near(sampler.sample_level(&texture, center(x, y, &texture), FilterMode::Point), expected);
+            // Anywhere inside the texel, including its top left edge, is the same texel
+            let corner = Vec2::new(x as f32, y as f32) / 4.0;
+            assert_near(sampler.sample_level(&texture, corner, FilterMode::Point), expected);
+            assert_near(sampler.sample_level(&texture, corner + Vec2::splat(0.249), FilterMode::Point), expected);
+        }
+    }
+}
+
+#[test]
+fn point_sampling_outside_the_texture_wraps() {
+    let texture = texture(4, 1, &[0, 64, 128, 192]);
+    let sample = |wrap_mode: WrapMode, u: f32| sampler(FilterMode::Point, wrap_mode).sample_level(&texture, Vec2::new(u, 0.5), FilterMode::Point);
+    assert_near(sample(WrapMode::Repeat, 1.125), grey(0.0));
+    assert_near(sample(WrapMode::Repeat, -0.125), grey(192.0));
+    assert_near(sample(WrapMode::Repeat, 3.375), grey(64.0));
+    assert_near(sample(WrapMode::Mirror, 1.125), grey(192.0));
+    assert_near(sample(WrapMode::Mirror, 1.875), grey(0.0));
+    assert_near(sample(WrapMode::Mirror, -0.125), grey(0.0));
+    assert_near(sample(WrapMode::Mirror, 2.125), grey(0.0));
+    assert_near(sample(WrapMode::Clamp, 1.125), grey(192.0));
+    assert_near(sample(WrapMode::Clamp, -5.0), grey(0.0));
+}
+
+#[test]
+fn bilinear_sampling_is_exact_at_texel_centers() {
+    let values: Vec<u8> = (0..12).map(|value| value * 20).collect();
+    let texture = texture(4, 3, &values);
+    for wrap_mode in [WrapMode::Repeat, WrapMode::Mirror, WrapMode::Clamp] {
+        let sampler = sampler(FilterMode::Linear, wrap_mode);
+        for y in 0..3 {
+            for x in 0..4 {
+                let sample = sampler.sample_level(&texture, center(x, y, &texture), FilterMode::Linear);
+                assert_near(sample, grey(values[x + y * 4] as f32));
+            }
+        }
+    }
+}
+
+#[test]
+fn bilinear_sampling_blends_between_texel_centers() {
+    let texture = texture(2, 2, &[0, 100, 200, 40]);
+    let sampler = sampler(FilterMode::Linear, WrapMode::Clamp);
+    let sample = |u: f32, v: f32| sampler.sample_level(&texture, Vec2::new(u, v), FilterMode::Linear);
+    // Halfway between both columns of the top row, and a quarter of the way
+    assert_near(sample(0.5, 0.25), grey(50.0));
+    assert_near(sample(0.375, 0.25), grey(25.0));
+    // Halfway between both rows of the left column
+    assert_near(sample(0.25, 0.5), grey(100.0));
+    // The center of the texture is the average of all four texels
+    assert_near(sample(0.5, 0.5), grey(85.0));
+}
+
+#[test]
+fn bilinear_sampling_blends_across_the_edge_with_the_wrap_mode() {
+    let texture = texture(4, 1, &[0, 64, 128, 192]);
+    let sample = |wrap_mode: WrapMode, u: f32| sampler(FilterMode::Linear, wrap_mode).sample_level(&texture, Vec2::new(u, 0.5), FilterMode::Linear);
+    // At the left edge, the sample is halfway between the first texel and whatever the wrap mode puts left of it
+    assert_near(sample(WrapMode::Repeat, 0.0), grey(96.0));
+    assert_near(sample(WrapMode::Mirror, 0.0), grey(0.0));
+    assert_near(sample(WrapMode::Clamp, 0.0), grey(0.0));
+    assert_near(sample(WrapMode::Repeat, 1.0), grey(96.0));
+    assert_near(sample(WrapMode::Mirror, 1.0), grey(192.0));
+    assert_near(sample(WrapMode::Clamp, 1.0), grey(192.0));
+}
+
+#[test]
+fn mip_chains_halve_down_to_one_texel() {
+    let base = texture(4, 2, &[0, 40, 80, 120, 160, 200, 240, 40]);
+    let mips = MipChain::new(&base);
+    let sizes: Vec<(usize, usize)> = mips.levels.iter().map(|level| (level.width, level.height)).collect();
+    assert_eq!(sizes, [(4, 2), (2, 1), (1, 1)]);
+    assert_eq!(mips.levels[0].data, base.data);
+    let sampler = sampler(FilterMode::Point, WrapMode::Clamp);
+    assert_near(sampler.texel(&mips.levels[1], 0, 0), grey(100.0));
+    assert_near(sampler.texel(&mips.levels[1], 1, 0), grey(120.0));
+    assert_near(sampler.texel(&mips.levels[2], 0, 0), grey(110.0));
+
+    assert_eq!(MipChain::new(&texture(1, 1, &[7])).levels.len(), 1);
+    assert_eq!(MipChain::new(&texture(8, 1, &[0; 8])).levels.len(), 4);
+    assert_eq!(MipChain::without_mips(&base).levels.len(), 1);
+}
+
+#[test]
+fn odd_sizes_round_down() {
+    let texture = texture(3, 3, &[0, 0, 36, 0, 0, 36, 72, 72, 144]);
+    let mips = MipChain::new(&texture);
+    let sizes: Vec<(usize, usize)> = mips.levels.iter().map(|level| (level.width, level.height)).collect();
+    assert_eq!(sizes, [(3, 3), (1, 1)]);
+    // The 1x1 level only covers the top left 2x2 texels
+    assert_near(sampler(FilterMode::Point, WrapMode::Clamp).texel(&mips.levels[1], 0, 0), grey(0.0));
+}
+
+#[test]
+fn level_of_detail_follows_the_largest_derivative() {
+    let size = UVec2::new(16, 8);
+    // One texel per pixel is the base level, every doubling is one level further
+    assert_eq!(level_of_detail(size, Vec2::new(1.0 / 16.0, 0.0), Vec2::new(0.0, 1.0 / 8.0)), 0.0);
+    assert_eq!(level_of_detail(size, Vec2::new(2.0 / 16.0, 0.0), Vec2::new(0.0, 1.0 / 8.0)), 1.0);
+    assert_eq!(level_of_detail(size, Vec2::new(1.0 / 16.0, 0.0), Vec2::new(0.0, 4.0 / 8.0)), 2.0);
+    assert_eq!(level_of_detail(size, Vec2::new(0.5 / 16.0, 0.0), Vec2::new(0.0, 0.5 / 8.0)), -1.0);
+    // Diagonal derivatives count their full length
+    assert!((level_of_detail(size, Vec2::new(1.0 / 16.0, 1.0 / 8.0), Vec2::ZERO) - 0.5).abs() < 1e-6);
+    assert_eq!(level_of_detail(size, Vec2::ZERO, Vec2::ZERO), f32::NEG_INFINITY);
+}
+
+// A 4x4 checkerboard of 0 and 64, with a smaller level of 100 and a 1x1 level of 200
+fn mip_test_texture() -> MipChain {
+    let mut mips = MipChain::new(&texture(4, 4, &[0, 64, 0, 64, 64, 0, 64, 0, 0, 64, 0, 64, 64, 0, 64, 0]));
+    mips.levels[1] = texture(2, 2, &[100; 4]);
+    mips.levels[2] = texture(1, 1, &[200]);
+    mips
+}
+
+// UV derivatives that cover 2^lod texels of the 4x4 base level per pixel
+fn derivatives(lod: f32) -> (Vec2, Vec2) {
+    let texels = 2f32.powf(lod) / 4.0;
+    (Vec2::new(texels, 0.0), Vec2::new(0.0, texels))
+}
+
+#[test]
+fn magnification_and_minification_use_their_own_filter() {
+    let mips = mip_test_texture();
+    let mut sampler = sampler(FilterMode::Point, WrapMode::Repeat);
+    sampler.filter_mode_min = FilterMode::Linear;
+    let uv = Vec2::new(0.25, 0.125);
+    // Magnified, the point filter returns the texel at (1, 0)
+    let (dx, dy) = derivatives(-1.0);
+    assert_near(sampler.sample(&mips, uv, dx, dy), grey(64.0));
+    assert_near(sampler.sample(&mips, uv, Vec2::ZERO, Vec2::ZERO), grey(64.0));
+    // Minified without mipmapping, the linear filter blends texels (0, 0) and (1, 0) of the base level
+    let (dx, dy) = derivatives(1.5);
+    assert_near(sampler.sample(&mips, uv, dx, dy), grey(32.0));
+}
+
+#[test]
+fn nearest_mipmap_filtering_picks_the_closest_level() {
+    let mips = mip_test_texture();
+    let mut sampler = sampler(FilterMode::Point, WrapMode::Repeat);
+    sampler.mipmap_enabled = true;
+    let uv = Vec2::new(0.375, 0.125);
+    for (lod, expected) in [(0.4, 64.0), (0.6, 100.0), (1.4, 100.0), (1.6, 200.0), (5.0, 200.0)] {
+        let (dx, dy) = derivatives(lod);
+        assert_near(sampler.sample(&mips, uv, dx, dy), grey(expected));
+    }
+}
+
+#[test]
+fn linear_mipmap_filtering_blends_between_levels() {
+    let mips = mip_test_texture();
+    let mut sampler = sampler(FilterMode::Linear, WrapMode::Repeat);
+    sampler.mipmap_enabled = true;
+    // The center of the base level blends 2 light and 2 dark texels, the other levels are a single brightness
+    let uv = Vec2::splat(0.5);
+    for (lod, expected) in [(0.25, 32.0 * 0.75 + 100.0 * 0.25), (1.0, 100.0), (1.5, 150.0), (1.75, 175.0), (2.0, 200.0), (8.0, 200.0)] {
+        let (dx, dy) = derivatives(lod);
+        assert_near(sampler.sample(&mips, uv, dx, dy), grey(expected));
+    }
+}
+
+#[test]
+fn textures_without_mips_sample_the_base_level() {
+    let texture = texture(2, 1, &[0, 100]);
+    let mips = MipChain::without_mips(&texture);
+    let mut sampler = sampler(FilterMode::Linear, WrapMode::Clamp);
+    sampler.mipmap_enabled = true;
+    let (dx, dy) = derivatives(3.0);
+    assert_near(sampler.sample(&mips, Vec2::new(0.5, 0.5), dx, dy), grey(50.0));
+}
+
+#[test]
+fn colors_are_filtered_per_channel() {
+    let mut texture = texture(2, 1, &[0, 0]);
+    texture.data = vec![u32::from_le_bytes([255, 0, 0, 255]), u32::from_le_bytes([0, 255, 0, 0])];
+    let sampler = sampler(FilterMode::Linear, WrapMode::Clamp);
+    let sample = sampler.sample_level(&texture, Vec2::new(0.5, 0.5), FilterMode::Linear);
+    assert_near(sample, Vec4::new(0.5, 0.5, 0.0, 0.5));
+    let mips = MipChain::new(&texture);
+    assert_eq!(mips.levels[1].data[0].to_le_bytes(), [128, 128, 0, 128]);
+}
+
+#[test]
+fn empty_textures_sample_as_transparent_black() {
+    let empty = Texture { width: 0, height: 3, data: Vec::new(), ..texture(1, 1, &[0]) };
+    let mips = MipChain::new(&empty);
+    assert_eq!(mips.levels.len(), 1);
+    for filter in [FilterMode::Point, FilterMode::Linear] {
+        let mut sampler = sampler(filter, WrapMode::Repeat);
+        sampler.mipmap_enabled = true;
+        assert_eq!(sampler.texel(&empty, 1, 1), Vec4::ZERO);
+        assert_eq!(sampler.sample_level(&empty, Vec2::splat(0.5), filter), Vec4::ZERO);
+        assert_eq!(sampler.sample(&mips, Vec2::splat(0.5), Vec2::ZERO, Vec2::ZERO), Vec4::ZERO);
+        assert_eq!(sampler.sample(&mips, Vec2::splat(0.5), Vec2::ONE, Vec2::ONE), Vec4::ZERO);
+    }
+}
+
+#[test]
+fn wrapping_onto_an_empty_axis_gives_0() {
+    for mode in [WrapMode::Repeat, WrapMode::Mirror, WrapMode::Clamp] {
+        assert_eq!(mode.apply(-1, 0), 0);
+        assert_eq!(mode.apply(5, 0), 0);
+    }
+}
+
+#[test]
+fn uvs_that_are_not_finite_sample_as_transparent_black() {
+    let texture = texture(2, 1, &[100, 200]);
+    for filter in [FilterMode::Point, FilterMode::Linear] {
+        let sampler = sampler(filter, WrapMode::Repeat);
+        // f32::MAX is finite, but not once it's scaled to the texture's width
+        for uv in [Vec2::new(f32::NAN, 0.5), Vec2::new(0.5, f32::INFINITY), Vec2::splat(f32::NEG_INFINITY), Vec2::new(f32::MAX, 0.5)] {
+            assert_eq!(sampler.sample_level(&texture, uv, filter), Vec4::ZERO);
+        }
+    }
+}
+
+#[test]
+fn huge_uvs_still_land_on_the_texture() {
+    let texture = texture(2, 1, &[100, 200]);
+    for wrap_mode in [WrapMode::Repeat, WrapMode::Mirror, WrapMode::Clamp] {
+        let sampler = sampler(FilterMode::Linear, wrap_mode);
+        for u in [1e30, -1e30, 1e20] {
+            let sample = sampler.sample_level(&texture, Vec2::new(u, 0.5), FilterMode::Linear);
+            assert!(sample.x >= 100.0 / 255.0 && sample.x <= 200.0 / 255.0, "{sample}");
+        }
+    }
+    // Clamping goes to the last texel in either direction
+    let sampler = sampler(FilterMode::Linear, WrapMode::Clamp);
+    assert_near(sampler.sample_level(&texture, Vec2::new(1e30, 0.5), FilterMode::Linear), grey(200.0));
+    assert_near(sampler.sample_level(&texture, Vec2::new(-1e30, 0.5), FilterMode::Linear), grey(100.0));
+}
+
+#[test]
+fn loaded_images_keep_red_in_the_red_channel() {
+    for (color_type, pixel) in [(png::ColorType::Rgba, &[255, 0, 0, 128][..]), (png::ColorType::Rgb, &[255, 0, 0][..])] {
+        let path = std::env::temp_dir().join(format!("rust_render_metal_red_{color_type:?}.png"));
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 1, 1);
+        encoder.set_color(color_type);
+        encoder.write_header().unwrap().write_image_data(pixel).unwrap();
+        std::fs::write(&path, png).unwrap();
+        let texture = Texture::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let texture = texture.unwrap();
+        let alpha = if color_type == png::ColorType::Rgba { 128 } else { 255 };
+        assert_eq!(texture.data[0].to_le_bytes(), [255, 0, 0, alpha]);
+        let sample = sampler(FilterMode::Point, WrapMode::Clamp).sample_level(&texture, Vec2::splat(0.5), FilterMode::Point);
+        assert_near(sample, Vec4::new(1.0, 0.0, 0.0, alpha as f32 / 255.0));
+    }
+}
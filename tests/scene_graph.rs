@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use glam::{Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
+use rust_render_metal::mesh::{Indices, Primitive, PrimitiveType};
+use rust_render_metal::structs::Vertex;
+use rust_render_metal::{
+    AssetError, Light, LightKind, Mesh, Model, ModelQueueEntry, Node, PlacedLight, RenderBackend, SoftwareRenderer, Transform,
+};
+
+fn load_hierarchy() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/node_hierarchy.gltf"), &mut renderer).unwrap()
+}
+
+#[test]
+fn nodes_keep_their_names_children_and_meshes() {
+    let model = load_hierarchy();
+    let car = &model.nodes[model.find_node("car").unwrap()];
+    let wheel = model.find_node("wheel").unwrap();
+    assert_eq!(model.root_nodes, [model.find_node("car").unwrap()]);
+    assert_eq!(car.children, [wheel, model.find_node("door").unwrap()]);
+    assert_eq!(car.mesh, None);
+    assert_eq!(model.nodes[wheel].mesh, Some(0));
+    assert_eq!(car.transform.translation, Vec3::X);
+    assert_eq!(model.nodes[model.find_node("door").unwrap()].transform.scale, Vec3::splat(2.0));
+}
+
+#[test]
+fn only_nodes_of_the_scene_are_visited() {
+    let model = load_hierarchy();
+    let names: Vec<&str> = model.scene_nodes().iter().map(|node| model.nodes[*node].name.as_str()).collect();
+    assert_eq!(names, ["car", "wheel", "hubcap", "door"]);
+}
+
+#[test]
+fn world_matrices_include_every_parent() {
+    let mut model = load_hierarchy();
+    let hubcap = model.find_node("hubcap").unwrap();
+    // car (1, 0, 0) + wheel (0, 1, 0) + hubcap (1, 0, 0) rotated 90 degrees by the wheel
+    assert!(model.nodes[hubcap].world_matrix.w_axis.truncate().abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1e-6));
+
+    let car = model.find_node("car").unwrap();
+    model.nodes[car].transform.translation = Vec3::ZERO;
+    model.update_world_matrices();
+    assert!(model.nodes[hubcap].world_matrix.w_axis.truncate().abs_diff_eq(Vec3::new(0.0, 2.0, 0.0), 1e-6));
+}
+
+#[test]
+fn moving_a_node_moves_what_is_drawn() {
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/node_hierarchy.gltf")).unwrap();
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    renderer.update_camera(&Transform {
+        translation: Vec3::new(0.0, 0.0, 5.0),
+        ..identity
+    });
+    let center = 8 + 8 * 16;
+
+    // The wheel holds a single point, which starts at (1, 1, 0)
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    assert_ne!(renderer.framebuffer()[center], 0xFFFFFFFF);
+
+    // Moving the car moves the wheel along with it, to the origin
+    let car = renderer.model(model).unwrap().find_node("car").unwrap();
+    renderer.model_mut(model).unwrap().nodes[car].transform.translation = Vec3::new(0.0, -1.0, 0.0);
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    assert_eq!(renderer.framebuffer()[center], 0xFFFFFFFF);
+}
+
+#[test]
+fn nodes_with_two_parents_are_rejected() {
+    let gltf = r#"{
+        "asset": {"version": "2.0"},
+        "scenes": [{"nodes": [0, 1]}],
+        "nodes": [{"children": [2]}, {"children": [2]}, {}]
+    }"#;
+    let path = std::env::temp_dir().join("rust_render_metal_two_parents.gltf");
+    std::fs::write(&path, gltf).unwrap();
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let result = renderer.load_model(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(AssetError::MalformedData(_))));
+}
+
+// Renders a single triangle with the same normal at every corner, lit by a directional light that shines along (1, 0, -2),
+// and returns the center pixel. The triangle covers the center of the 16x16 framebuffer.
+fn render_lit_triangle(positions: [Vec3; 3], normal: Vec3, scale: Vec3) -> u32 {
+    let verts: Vec<Vertex> = positions
+        .iter()
+        .map(|position| Vertex {
+            position: *position,
+            normal,
+            tangent: Vec4::new(0.0, 1.0, 0.0, 1.0),
+            color: Vec4::ONE,
+            uv0: Vec2::ZERO,
+            uv1: Vec2::ZERO,
+            joints: UVec4::ZERO,
+            weights: Vec4::ZERO,
+        })
+        .collect();
+    let mut mesh = Mesh {
+        indices: Indices::new(vec![0, 1, 2], verts.len()),
+        verts,
+        primitive_type: PrimitiveType::Triangles,
+        morph_targets: Vec::new(),
+        buffer: None,
+    };
+
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    renderer.upload_vertex_buffer(&mut mesh);
+    let mut model = Model::new();
+    model.meshes.push(vec![Primitive { mesh, material: None }]);
+    model.nodes.push(Node {
+        name: "triangle".to_string(),
+        transform: identity,
+        children: Vec::new(),
+        mesh: Some(0),
+        skin: None,
+        camera: None,
+        light: None,
+        weights: Vec::new(),
+        world_matrix: Mat4::IDENTITY,
+    });
+    model.root_nodes.push(0);
+    let model = renderer.store_model(model);
+
+    let sun = Light {
+        name: "sun".to_string(),
+        kind: LightKind::Directional,
+        color: Vec3::ONE,
+        intensity: 1.0,
+        range: None,
+    };
+    renderer.set_lights(vec![PlacedLight { light: sun, position: Vec3::ZERO, direction: Vec3::new(1.0, 0.0, -2.0).normalize() }]);
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: Transform { scale, ..identity } });
+    renderer.end_frame();
+    renderer.framebuffer()[8 + 8 * 16]
+}
+
+#[test]
+fn normals_stay_perpendicular_under_non_uniform_scale() {
+    // A triangle in the plane x + z = 0, stretched to twice its width. That turns it into the plane x / 2 + z = 0,
+    // so it should shade the same as a triangle that's already in that plane. Stretching the normal along with the
+    // triangle would turn it to (2, 0, 1), which is perpendicular to the light and would shade black.
+    let stretched = render_lit_triangle(
+        [Vec3::new(-0.5, -1.0, 0.5), Vec3::new(0.5, -1.0, -0.5), Vec3::new(0.0, 1.0, 0.0)],
+        Vec3::new(1.0, 0.0, 1.0).normalize(),
+        Vec3::new(2.0, 1.0, 1.0),
+    );
+    let expected = render_lit_triangle(
+        [Vec3::new(-1.0, -1.0, 0.5), Vec3::new(1.0, -1.0, -0.5), Vec3::new(0.0, 1.0, 0.0)],
+        Vec3::new(1.0, 0.0, 2.0).normalize(),
+        Vec3::ONE,
+    );
+    assert_eq!(stretched.to_le_bytes(), expected.to_le_bytes());
+    assert!(expected.to_le_bytes()[0] > 50, "{:?}", expected.to_le_bytes());
+}
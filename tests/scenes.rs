@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use glam::{Quat, Vec3};
+use rust_render_metal::{Model, ModelQueueEntry, RenderBackend, SoftwareRenderer, Transform};
+
+const WHITE: u32 = 0xFFFFFFFF;
+
+fn load(path: &str) -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new(path), &mut renderer).unwrap()
+}
+
+#[test]
+fn scenes_are_listed() {
+    let model = load("./tests/assets/scenes.gltf");
+    let names: Vec<&str> = model.scenes.iter().map(|scene| scene.name.as_str()).collect();
+    assert_eq!(names, ["first", "second", "untitled"]);
+    assert_eq!(model.scenes[1].root_nodes, [1, 2]);
+    assert!(model.scenes[2].root_nodes.is_empty());
+    assert_eq!(model.find_scene("second"), Some(1));
+    assert_eq!(model.find_scene("third"), None);
+}
+
+#[test]
+fn first_scene_is_used_without_a_default() {
+    let model = load("./tests/assets/scenes.gltf");
+    assert_eq!(model.scene, Some(0));
+    assert_eq!(model.root_nodes, [model.find_node("left").unwrap()]);
+}
+
+#[test]
+fn default_scene_is_used_when_set() {
+    let model = load("./tests/assets/scenes_default.gltf");
+    assert_eq!(model.scene, Some(1));
+    assert_eq!(model.root_nodes, [1, 2]);
+}
+
+#[test]
+fn switching_scenes_updates_the_world_matrices() {
+    let mut model = load("./tests/assets/scenes.gltf");
+    let child = model.find_node("right_child").unwrap();
+    assert!(model.set_scene(model.find_scene("second").unwrap()));
+    assert_eq!(model.scene, Some(1));
+    assert_eq!(model.scene_nodes(), [1, 3, 2]);
+    assert!((model.nodes[child].world_matrix.w_axis.truncate() - Vec3::new(1.0, 0.0, 2.0)).length() < 1e-6);
+
+    // Empty scenes are fine, they just don't draw anything
+    assert!(model.set_scene(2));
+    assert!(model.scene_nodes().is_empty());
+}
+
+#[test]
+fn missing_scenes_keep_the_current_one() {
+    let mut model = load("./tests/assets/scenes.gltf");
+    assert!(!model.set_scene(3));
+    assert_eq!(model.scene, Some(0));
+    assert_eq!(model.root_nodes, [0]);
+}
+
+#[test]
+fn only_the_selected_scene_is_drawn() {
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/scenes.gltf")).unwrap();
+    renderer.update_camera(&Transform { translation: Vec3::new(0.0, 0.0, 5.0), ..identity });
+    let draw = |renderer: &mut SoftwareRenderer| {
+        renderer.begin_frame();
+        renderer.draw_model(ModelQueueEntry { model, transform: identity });
+        renderer.end_frame();
+        renderer.framebuffer().to_vec()
+    };
+
+    let framebuffer = draw(&mut renderer);
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 1);
+    assert_eq!(framebuffer[4 + 8 * 16], WHITE);
+
+    assert!(renderer.model_mut(model).unwrap().set_scene(1));
+    let framebuffer = draw(&mut renderer);
+    assert_eq!(framebuffer.iter().filter(|pixel| **pixel == WHITE).count(), 2);
+    assert_eq!(framebuffer[11 + 8 * 16], WHITE);
+    assert_eq!(framebuffer[8 + 4 * 16], WHITE);
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use glam::{Mat4, Quat, UVec4, Vec3, Vec4};
+use rust_render_metal::skin::skin_vertex;
+use rust_render_metal::{AssetError, Model, ModelQueueEntry, RenderBackend, SoftwareRenderer, Transform};
+
+fn load_skinned() -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf(Path::new("./tests/assets/skinned_points.gltf"), &mut renderer).unwrap()
+}
+
+fn skinned_positions(model: &Model) -> Vec<Vec3> {
+    let joint_matrices = model.skins[0].joint_matrices(&model.nodes);
+    model.meshes[0][0].mesh.verts.iter().map(|vertex| skin_vertex(vertex, &joint_matrices).position).collect()
+}
+
+#[test]
+fn skins_and_vertex_joints_are_loaded() {
+    let model = load_skinned();
+    let skin = &model.skins[0];
+    assert_eq!(skin.name, "skeleton");
+    assert_eq!(skin.joints, [model.find_node("bone_a").unwrap(), model.find_node("bone_b").unwrap()]);
+    assert_eq!(skin.inverse_bind_matrices, [Mat4::IDENTITY, Mat4::from_translation(Vec3::NEG_Y)]);
+    assert_eq!(model.nodes[model.find_node("body").unwrap()].skin, Some(0));
+
+    let verts = &model.meshes[0][0].mesh.verts;
+    assert_eq!(verts[1].joints, UVec4::new(1, 0, 0, 0));
+    assert_eq!(verts[2].weights, Vec4::new(0.5, 0.5, 0.0, 0.0));
+}
+
+#[test]
+fn bind_pose_leaves_vertices_in_place() {
+    let model = load_skinned();
+    assert_eq!(skinned_positions(&model), [Vec3::ZERO, Vec3::Y, Vec3::new(0.0, 0.5, 0.0)]);
+}
+
+#[test]
+fn vertices_follow_their_joints() {
+    let mut model = load_skinned();
+    let bone_a = model.find_node("bone_a").unwrap();
+    model.nodes[bone_a].transform.rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    model.update_world_matrices();
+
+    let expected = [Vec3::ZERO, Vec3::NEG_X, Vec3::new(-0.5, 0.0, 0.0)];
+    for (position, expected) in skinned_positions(&model).iter().zip(expected) {
+        assert!(position.abs_diff_eq(expected, 1e-6), "{position} != {expected}");
+    }
+}
+
+#[test]
+fn renderer_draws_skinned_vertices() {
+    let mut renderer = SoftwareRenderer::new(16, 16);
+    let model = renderer.load_model(Path::new("./tests/assets/skinned_points.gltf")).unwrap();
+    let identity = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+    renderer.update_camera(&Transform {
+        translation: Vec3::new(0.0, 0.0, 5.0),
+        ..identity
+    });
+    let white = |renderer: &SoftwareRenderer, x: usize, y: usize| renderer.framebuffer()[x + y * 16] == 0xFFFFFFFF;
+
+    // The body node is translated, but skinned meshes ignore that, so the first vertex is in the center
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    assert!(white(&renderer, 8, 8));
+    assert!(white(&renderer, 8, 4));
+    assert!(!white(&renderer, 4, 8));
+
+    // Bending the skeleton moves the vertex attached to bone_b to (-1, 0, 0)
+    let bone_a = renderer.model(model).unwrap().find_node("bone_a").unwrap();
+    renderer.model_mut(model).unwrap().nodes[bone_a].transform.rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    renderer.begin_frame();
+    renderer.draw_model(ModelQueueEntry { model, transform: identity });
+    renderer.end_frame();
+    assert!(white(&renderer, 8, 8));
+    assert!(!white(&renderer, 8, 4));
+    assert!(white(&renderer, 4, 8));
+}
+
+#[test]
+fn joints_outside_the_skin_are_rejected() {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    let result = renderer.load_model(Path::new("./tests/assets/skin_bad_joint.gltf"));
+    assert!(matches!(result, Err(AssetError::MalformedData(_))));
+}
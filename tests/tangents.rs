@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use glam::{UVec4, Vec2, Vec3, Vec4};
+use rust_render_metal::mesh::{Indices, PrimitiveType};
+use rust_render_metal::structs::Vertex;
+use rust_render_metal::{LoadOptions, Mesh, Model, SoftwareRenderer};
+
+fn load(options: &LoadOptions) -> Model {
+    let mut renderer = SoftwareRenderer::new(4, 4);
+    Model::load_gltf_with_options(Path::new("./tests/assets/tangents.gltf"), &mut renderer, options).unwrap()
+}
+
+fn tangents_of(model: &Model, material: &str) -> Vec<Vec4> {
+    let primitive = model.meshes[0].iter().find(|primitive| primitive.material == model.find_material(material)).unwrap();
+    primitive.mesh.verts.iter().map(|vertex| vertex.tangent).collect()
+}
+
+fn assert_all_near(tangents: &[Vec4], expected: Vec4) {
+    for tangent in tangents {
+        assert!((*tangent - expected).length() < 1e-5, "{tangent} should be {expected}");
+    }
+}
+
+#[test]
+fn tangents_follow_u_with_v_pointing_down() {
+    // A quad with the image upright, like most exporters write it, is right handed
+    let model = load(&LoadOptions::default());
+    assert_all_near(&tangents_of(&model, "quad"), Vec4::new(1.0, 0.0, 0.0, 1.0));
+}
+
+#[test]
+fn mirrored_uvs_flip_the_handedness() {
+    let model = load(&LoadOptions::default());
+    assert_all_near(&tangents_of(&model, "mirrored"), Vec4::new(-1.0, 0.0, 0.0, -1.0));
+}
+
+#[test]
+fn tangents_are_orthogonal_to_the_normal() {
+    let model = load(&LoadOptions::default());
+    assert_all_near(&tangents_of(&model, "tilted"), Vec4::new(0.8, 0.0, -0.6, 1.0));
+}
+
+#[test]
+fn existing_tangents_are_kept_unless_asked_to_regenerate() {
+    let model = load(&LoadOptions::default());
+    assert_all_near(&tangents_of(&model, "with_tangents"), Vec4::new(0.0, 1.0, 0.0, 1.0));
+
+    let model = load(&LoadOptions { regenerate_tangents: true, ..Default::default() });
+    assert_all_near(&tangents_of(&model, "with_tangents"), Vec4::new(1.0, 0.0, 0.0, 1.0));
+}
+
+#[test]
+fn degenerate_uvs_still_get_a_tangent_space() {
+    let model = load(&LoadOptions::default());
+    for tangent in tangents_of(&model, "no_uvs") {
+        assert!((tangent.truncate().length() - 1.0).abs() < 1e-5);
+        assert!(tangent.truncate().dot(Vec3::Z).abs() < 1e-5);
+        assert_eq!(tangent.w.abs(), 1.0);
+    }
+}
+
+fn vertex(position: Vec3, uv: Vec2) -> Vertex {
+    Vertex {
+        position,
+        normal: Vec3::Z,
+        tangent: Vec4::ZERO,
+        color: Vec4::ONE,
+        uv0: uv,
+        uv1: Vec2::ZERO,
+        joints: UVec4::ZERO,
+        weights: Vec4::ZERO,
+    }
+}
+
+#[test]
+fn shared_vertices_weigh_triangles_by_corner_angle() {
+    // Both triangles share the vertex at the origin. The first has a 90 degree corner there with u along X,
+    // the second has a 45 degree corner with u along Y.
+    let verts = vec![
+        vertex(Vec3::ZERO, Vec2::ZERO),
+        vertex(Vec3::X, Vec2::new(1.0, 0.0)),
+        vertex(Vec3::Y, Vec2::new(0.0, 1.0)),
+        vertex(Vec3::NEG_Y, Vec2::new(-1.0, 0.0)),
+        vertex(Vec3::new(1.0, -1.0, 0.0), Vec2::new(-1.0, -1.0)),
+    ];
+    let mut mesh = Mesh {
+        indices: Indices::new(vec![0, 1, 2, 0, 3, 4], verts.len()),
+        verts,
+        primitive_type: PrimitiveType::Triangles,
+        morph_targets: Vec::new(),
+        buffer: None,
+    };
+    mesh.generate_tangents();
+    let expected = Vec3::new(2.0, 1.0, 0.0).normalize().extend(-1.0);
+    assert!((mesh.verts[0].tangent - expected).length() < 1e-5, "{}", mesh.verts[0].tangent);
+}
+
+#[test]
+fn only_triangles_get_tangents() {
+    let mut mesh = Mesh {
+        indices: Indices::new(vec![0, 1], 2),
+        verts: vec![vertex(Vec3::ZERO, Vec2::ZERO), vertex(Vec3::X, Vec2::X)],
+        primitive_type: PrimitiveType::Lines,
+        morph_targets: Vec::new(),
+        buffer: None,
+    };
+    mesh.generate_tangents();
+    assert!(mesh.verts.iter().all(|vertex| vertex.tangent == Vec4::ZERO));
+}
+
+#[test]
+fn mirror_seams_split_vertices_by_handedness() {
+    // A quad whose UVs are mirrored at x = 0: u goes from 1 to 0 on the left half, and back to 1 on the right half.
+    // The two vertices on the seam are shared by both halves.
+    let verts = [-1.0, 0.0, 1.0, -1.0, 0.0, 1.0]
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let y = if i < 3 { -1.0 } else { 1.0 };
+            vertex(Vec3::new(*x, y, 0.0), Vec2::new(f32::abs(*x), (1.0 - y) / 2.0))
+        })
+        .collect::<Vec<Vertex>>();
+    let mut mesh = Mesh {
+        indices: Indices::new(vec![0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4], verts.len()),
+        verts,
+        primitive_type: PrimitiveType::Triangles,
+        morph_targets: Vec::new(),
+        buffer: None,
+    };
+    mesh.generate_tangents();
+
+    // Both seam vertices get a copy, so each half keeps its own tangent space
+    assert_eq!(mesh.verts.len(), 8);
+    let indices = mesh.indices.to_u32();
+    let (left, right) = indices.split_at(6);
+    let tangents = |corners: &[u32]| corners.iter().map(|index| mesh.verts[*index as usize].tangent).collect::<Vec<Vec4>>();
+    assert_all_near(&tangents(left), Vec4::new(-1.0, 0.0, 0.0, -1.0));
+    assert_all_near(&tangents(right), Vec4::new(1.0, 0.0, 0.0, 1.0));
+}